@@ -0,0 +1,59 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+
+/// The time after which long-running operations are aborted.
+static DEADLINE: OnceLock<Instant> = OnceLock::new();
+
+/// Aborts long-running operations once `timeout` has passed from now.
+pub fn set(timeout: Duration) {
+    if let Some(deadline) = Instant::now().checked_add(timeout) {
+        let _ = DEADLINE.set(deadline);
+    }
+}
+
+/// Returns an error if the deadline has passed.
+///
+/// Long-running loops call this periodically so they can be aborted.
+pub fn check() -> Result<()> {
+    match DEADLINE.get() {
+        Some(deadline) if Instant::now() >= *deadline => Err("the operation timed out".into()),
+        _ => Ok(()),
+    }
+}
+
+/// Like [`check`], but only looks at the clock once every 4096 iterations.
+///
+/// Loops with very fast iterations call this with the index of every
+/// iteration, so checking the time doesn't slow them down.
+pub fn check_every(i: usize) -> Result<()> {
+    if i.is_multiple_of(4096) {
+        check()
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses a duration like `500ms`, `5s`, `2m` or `1h`.
+///
+/// A number without a unit is in seconds.
+pub fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+
+    let secs = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return Err(format!("invalid unit `{unit}`, expected `ms`, `s`, `m` or `h`")),
+    };
+
+    Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())
+}
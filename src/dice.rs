@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+use rand::Rng;
+
+/// The largest number of dice in a single term of an expression.
+const MAX_DICE: u64 = 10_000;
+
+/// A sum of dice rolls and constants, like `2d6+1d4-1`.
+#[derive(Debug, Clone)]
+pub struct Expr(Vec<Term>);
+
+/// A signed part of an [`Expr`].
+#[derive(Debug, Clone, Copy)]
+enum Term {
+    /// `count` dice with `sides` sides each, subtracted if `negative` is set.
+    Dice { count: u64, sides: u64, negative: bool },
+    /// A constant.
+    Constant(i128),
+}
+
+impl Term {
+    /// Parses a term without its sign, like `2d6`, `d20` or `5`.
+    fn parse(s: &str, negative: bool) -> Result<Self, String> {
+        let invalid = || format!("invalid dice term `{s}`");
+
+        let Some((count, sides)) = s.split_once('d') else {
+            let constant = s.parse::<i128>().map_err(|_| invalid())?;
+            return Ok(Self::Constant(if negative { -constant } else { constant }));
+        };
+
+        let count = if count.is_empty() { 1 } else { count.parse().map_err(|_| invalid())? };
+        let sides = sides.parse::<u64>().map_err(|_| invalid())?;
+
+        if sides == 0 {
+            return Err(format!("the dice of `{s}` must have at least 1 side"));
+        }
+        if count > MAX_DICE {
+            return Err(format!("`{s}` rolls more than {MAX_DICE} dice"));
+        }
+
+        Ok(Self::Dice {
+            count,
+            sides,
+            negative,
+        })
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expr = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if expr.is_empty() {
+            return Err("empty dice expression".to_owned());
+        }
+
+        let (mut negative, mut rest) = match expr.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, expr.strip_prefix('+').unwrap_or(&expr)),
+        };
+        let mut terms = Vec::new();
+
+        loop {
+            let (term, tail) = rest.split_at(rest.find(['+', '-']).unwrap_or(rest.len()));
+            terms.push(Term::parse(term, negative)?);
+
+            let Some(sign) = tail.chars().next() else {
+                break;
+            };
+            negative = sign == '-';
+            rest = &tail[1..];
+        }
+
+        Ok(Self(terms))
+    }
+}
+
+impl Expr {
+    /// Rolls the dice of the expression and returns the total.
+    pub fn roll(&self, rng: &mut impl Rng) -> i128 {
+        self.0
+            .iter()
+            .map(|term| match *term {
+                Term::Dice {
+                    count,
+                    sides,
+                    negative,
+                } => {
+                    let sum = (0..count)
+                        .map(|_| i128::from(rng.gen_range(1..=sides)))
+                        .sum::<i128>();
+                    if negative {
+                        -sum
+                    } else {
+                        sum
+                    }
+                },
+                Term::Constant(constant) => constant,
+            })
+            .fold(0, i128::saturating_add)
+    }
+}
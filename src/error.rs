@@ -1,33 +1,107 @@
 use std::fmt::Display;
-use std::io::Write;
+use std::io::{stderr, IsTerminal, Write};
+use std::sync::OnceLock;
+use std::{env, panic};
 
 use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
+use crate::output::Format;
+
 /// Result type used throughout the crate.
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// The color choice for messages printed on the `stderr`.
+static STDERR_COLOR: OnceLock<ColorChoice> = OnceLock::new();
+
+/// Sets the color choice for errors and warnings printed on the `stderr`.
+///
+/// If this isn't called, colors are used if the `stderr` allows them.
+pub fn set_stderr_color(color: ColorChoice) {
+    let _ = STDERR_COLOR.set(color);
+}
+
+/// Makes panics exit with an error message instead of printing a backtrace.
+///
+/// The default panic message and backtrace are kept if `RUST_BACKTRACE` is set
+/// to anything but `0`.
+pub fn set_panic_hook(format: Format) {
+    if env::var_os("RUST_BACKTRACE").is_some_and(|v| v != "0") {
+        return;
+    }
+
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let msg = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        exit(format_args!("internal error: {msg}"), 101, format);
+    }));
+}
+
+/// Returns the color choice for an output stream.
+///
+/// Colors are disabled if `no_color` is true, the `NO_COLOR` environment variable
+/// is set, `CLICOLOR` is set to `0` or the stream is not a terminal. Setting
+/// `CLICOLOR_FORCE` to anything but `0` enables colors even if the stream is not
+/// a terminal.
+pub fn color_choice(no_color: bool, is_terminal: bool) -> ColorChoice {
+    let clicolor = |var| env::var_os(var).map(|v| v != "0");
+
+    if no_color || env::var_os("NO_COLOR").is_some() || clicolor("CLICOLOR") == Some(false) {
+        ColorChoice::Never
+    } else if clicolor("CLICOLOR_FORCE") == Some(true) {
+        ColorChoice::Always
+    } else if is_terminal {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    }
+}
+
 /// Prints the error on the `stderr` and exits with the provided exit code.
 ///
 /// "error: " is displayed before the error message. The "error" is displayed in
 /// red and bold if possible.
-pub fn exit<T: Display>(err: T, code: i32) -> ! {
-    print_error(&err).unwrap_or_else(|_| eprintln!("error: {}", err));
+///
+/// If `format` is a JSON format, the error is printed as a JSON object with an
+/// `error` key instead.
+pub fn exit<T: Display>(err: T, code: i32, format: Format) -> ! {
+    match format {
+        Format::Json | Format::JsonPretty | Format::Ndjson => {
+            eprintln!("{}", serde_json::json!({ "error": err.to_string() }))
+        },
+        _ => {
+            print_message("error", Color::Red, &err).unwrap_or_else(|_| eprintln!("error: {}", err))
+        },
+    }
     std::process::exit(code);
 }
 
-/// Prints error on the `stderr`.
+/// Prints the warning on the `stderr`.
 ///
-/// "error: " is displayed before the error message. The "error" is displayed in
-/// red and bold if possible.
-fn print_error<T: Display>(err: &T) -> Result<()> {
-    let bufwtr = BufferWriter::stderr(ColorChoice::Auto);
+/// "warning: " is displayed before the warning message. The "warning" is displayed
+/// in yellow and bold if possible.
+pub fn warn<T: Display>(warning: T) {
+    print_message("warning", Color::Yellow, &warning)
+        .unwrap_or_else(|_| eprintln!("warning: {}", warning));
+}
+
+/// Prints a message on the `stderr`.
+///
+/// "`label`: " is displayed before the message. The `label` is displayed in
+/// `color` and bold if possible.
+fn print_message<T: Display>(label: &str, color: Color, msg: &T) -> Result<()> {
+    let choice = *STDERR_COLOR.get_or_init(|| color_choice(false, stderr().is_terminal()));
+    let bufwtr = BufferWriter::stderr(choice);
     let mut buffer = bufwtr.buffer();
 
-    buffer.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+    buffer.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
 
-    write!(&mut buffer, "error")?;
+    write!(&mut buffer, "{label}")?;
     buffer.reset()?;
-    writeln!(&mut buffer, ": {}", err)?;
+    writeln!(&mut buffer, ": {}", msg)?;
 
     bufwtr.print(&buffer)?;
 
@@ -1,20 +1,67 @@
+mod deadline;
+mod dice;
 mod error;
+mod output;
+mod weights;
 
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::fs::{self, OpenOptions};
 use std::hash::Hash;
+use std::io::{sink, stderr, stdin, stdout, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, iter};
 
-use clap::{Parser, Subcommand, ValueEnum};
-use error::{exit, Result};
+use clap::{crate_name, Args, Parser, Subcommand, ValueEnum};
+use error::{color_choice, exit, set_panic_hook, set_stderr_color, warn, Result};
 use itertools::Itertools;
+use output::{CountFormat, CountSnapshots, Counts, Format, LiveHistogram, Output, Section, Value};
 use rand::distributions::uniform::SampleUniform;
 use rand::distributions::{Alphanumeric, DistString, Uniform, WeightedIndex};
 use rand::prelude::{Distribution, SliceRandom};
-use rand::Rng;
+use rand::rngs::OsRng;
+use rand::seq::index;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
 const ABOUT: &str = "rnd lets you select random data in different ways.";
 const AMOUNT_THRESHOLD: usize = 10;
+/// The largest useful precision of an `f64`.
+const MAX_PRECISION: usize = 17;
+const COIN_SIDES: [&str; 2] = ["heads", "tails"];
+/// The consonants of pronounceable strings, leaving out the ones that are easy to
+/// misread or mispronounce.
+const CONSONANTS: &[u8] = b"bdfghjklmnprstvz";
+/// The vowels of pronounceable strings.
+const VOWELS: &[u8] = b"aeiou";
+/// The characters left out of strings with `--no-ambiguous`.
+const AMBIGUOUS: &str = "0O1lI";
+
+/// Example invocations and their descriptions, printed by the `examples` command.
+const EXAMPLES: &[(&str, &str)] = &[
+    ("rnd", "Print a random number between 0 and 1"),
+    ("rnd random 1 100", "Print a random integer between 1 and 100 (not inclusive)"),
+    ("rnd random -i 1.5 2.5 -p 2", "Print a random float between 1.5 and 2.5 with 2 decimals"),
+    ("rnd coin 100 -c", "Flip a coin 100 times and count heads and tails"),
+    ("rnd choose red green blue", "Choose one of the items"),
+    ("rnd choose a b c -n 2 -w 1,2,3", "Choose two different items with weights"),
+    ("rnd choose a b -n 1000 -r --top 1", "Choose with repetition and show the most common item"),
+    ("rnd shuffle a b c d", "Shuffle a list of items"),
+    ("rnd die 20 -n 3", "Roll a 20-sided die three times"),
+    ("rnd die --faces +,+,-,-,0,0 -n 4", "Roll four Fudge dice"),
+    ("rnd die 20 -n 2 --keep-highest 1", "Roll a 20-sided die with advantage"),
+    ("rnd string -n 16 -c mixed", "Generate a mixed-case alphanumeric string of length 16"),
+    ("rnd assign -l alice,bob -r tea,coffee", "Randomly assign drinks to people"),
+    ("rnd table 1-50:common 51-90:rare 91-100:epic", "Roll on a random table"),
+    ("cat log.txt | rnd reservoir -n 10", "Choose ten random lines from a stream"),
+    ("echo 1d20+5 | rnd eval", "Roll a dice expression"),
+    ("rnd --seed 42 die -n 5", "Roll dice reproducibly"),
+    ("rnd --format json coin 20 -c", "Print the output as JSON"),
+];
 
 const HELP_TEMPLATE: &str = r"{before-help}{bin} {version}
 {author}
@@ -35,6 +82,357 @@ struct Cli {
     /// The subcommand.
     #[clap(subcommand)]
     command: Option<Command>,
+    /// The format of the output.
+    ///
+    /// By default, the output is colored if it is written to a terminal.
+    #[clap(long, global = true, default_value_t = Format::Auto, value_enum)]
+    format: Format,
+    /// Print the output as indented JSON.
+    ///
+    /// This is the same as `--format json-pretty` and overrides `--format`.
+    #[clap(long, global = true, conflicts_with = "json-compact")]
+    json: bool,
+    /// Print the output as single-line JSON.
+    ///
+    /// This is the same as `--format json` and overrides `--format`.
+    #[clap(long, global = true)]
+    json_compact: bool,
+    /// Write the output to a file instead of the `stdout`.
+    #[clap(short, long, global = true, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+    /// Append to the output file instead of overwriting it.
+    #[clap(long, global = true, requires = "output-file")]
+    append: bool,
+    /// Overwrite the output file without asking if it already exists.
+    ///
+    /// Without this flag, you are asked before overwriting the file, or an error
+    /// is returned if the `stdin` is not a terminal.
+    #[clap(short, long, global = true)]
+    force: bool,
+    /// A line printed before the output, like a header.
+    #[clap(long, global = true, value_name = "LINE", allow_hyphen_values = true)]
+    prefix_line: Option<String>,
+    /// A line printed after the output, like a terminator.
+    #[clap(long, global = true, value_name = "LINE", allow_hyphen_values = true)]
+    suffix_line: Option<String>,
+    /// Disable colors in the output.
+    #[clap(long, global = true)]
+    no_color: bool,
+    /// The seed for the random number generator.
+    ///
+    /// Using the same seed with the same command produces the same output. The
+    /// seed can be decimal or hexadecimal with a `0x` prefix.
+    ///
+    /// Seeded numbers are generated with ChaCha20, so the output is the same on
+    /// every platform.
+    #[clap(long, global = true, conflicts_with = "secure", value_parser = parse_seed)]
+    seed: Option<u64>,
+    /// The stream of random numbers to use for the seed.
+    ///
+    /// Different streams of the same seed produce independent, but reproducible,
+    /// outputs.
+    #[clap(long, global = true, requires = "seed")]
+    stream: Option<u64>,
+    /// Mix a value of the running process into the seed.
+    ///
+    /// The same command then produces different outputs on different machines
+    /// or processes. Use `--print-seed` to log the derived seed, which
+    /// reproduces the output with `--seed`.
+    #[clap(long, global = true, value_enum, value_name = "SOURCE", requires = "seed")]
+    seed_entropy: Option<SeedEntropy>,
+    /// Print the seed used by the command on the `stderr`.
+    ///
+    /// If no seed is provided, a random one is used and printed.
+    #[clap(long, global = true, conflicts_with = "secure")]
+    print_seed: bool,
+    /// Read the seed from a file, or save a random seed to it if it doesn't exist.
+    #[clap(
+        long,
+        global = true,
+        value_name = "FILE",
+        conflicts_with_all = &["seed", "secure"]
+    )]
+    seed_file: Option<PathBuf>,
+    /// Increment the seed in the seed file after reading it, so the next run
+    /// uses a different but reproducible seed.
+    #[clap(long, global = true, requires = "seed-file")]
+    advance_seed: bool,
+    /// Use the operating system's cryptographically secure random number generator.
+    ///
+    /// It can't be seeded, so this can't be used with `--seed`.
+    #[clap(long, global = true)]
+    secure: bool,
+    /// Print the arguments and seed of the command as JSON instead of running it.
+    ///
+    /// The parameters can be run again with the `replay` command. If no seed is
+    /// provided, a random one is used.
+    #[clap(long, global = true, conflicts_with = "secure")]
+    emit_params: bool,
+    /// Only generate ASCII characters in strings.
+    ///
+    /// Returns an error if a string would contain a character that isn't ASCII.
+    #[clap(long, global = true)]
+    ascii_only: bool,
+    /// Abort long-running operations after this long, like `500ms`, `5s` or `2m`.
+    #[clap(long, global = true, value_name = "DURATION", value_parser = deadline::parse_duration)]
+    timeout: Option<Duration>,
+    /// The largest amount of values a command may generate.
+    ///
+    /// This guards against typos that would exhaust the memory or run for a
+    /// very long time.
+    #[clap(long, global = true, value_name = "N", default_value_t = 1_000_000_000)]
+    max_amount: usize,
+    /// Print the seed, the random number generator and the time taken on the
+    /// `stderr`.
+    ///
+    /// If no seed is provided, a random one is used and printed, so the run can
+    /// be reproduced with `--seed`.
+    #[clap(long, global = true)]
+    verbose: bool,
+    /// Check the arguments of the command without printing anything.
+    ///
+    /// Exits successfully if the command would run. Nothing is generated, the
+    /// output file isn't opened and the seed file isn't changed.
+    #[clap(long, global = true)]
+    dry_run: bool,
+    /// Print the name and version as JSON.
+    #[clap(long)]
+    version_json: bool,
+}
+
+/// The global options that change how a command runs.
+#[derive(Debug, Clone, Copy)]
+struct RunOptions {
+    /// Only generate ASCII characters in strings.
+    ascii_only: bool,
+    /// The largest amount of values a command may generate.
+    max_amount: usize,
+    /// Check the arguments without generating anything.
+    dry_run: bool,
+    /// The seed used to shuffle items with equal counts.
+    tie_seed: u64,
+}
+
+/// The parameters of a run, printed by `--emit-params` and read by `replay`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Params {
+    /// The seed of the random number generator.
+    seed: u64,
+    /// The command-line arguments of the run, without the program name.
+    args: Vec<String>,
+}
+
+impl Cli {
+    /// Returns the writer the output is written to.
+    fn writer(&self) -> Result<Box<dyn Write>> {
+        match &self.output_file {
+            Some(path) => {
+                if !self.append && !self.force && path.exists() {
+                    confirm_overwrite(path)?;
+                }
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .append(self.append)
+                    .truncate(!self.append)
+                    .open(path)
+                    .map_err(|e| format!("failed to open `{}`: {e}", path.display()))?;
+                Ok(Box::new(BufWriter::new(file)))
+            },
+            None => Ok(Box::new(stdout().lock())),
+        }
+    }
+
+    /// Describes the random number generator returned by [`Cli::rng`].
+    fn rng_description(&self) -> String {
+        match (self.secure, self.seed, self.stream) {
+            (true, ..) => "rng: OsRng (unseeded)".to_owned(),
+            (_, Some(seed), Some(stream)) => {
+                format!("seed: {seed}, stream: {stream}, rng: ChaCha20")
+            },
+            (_, Some(seed), None) => format!("seed: {seed}, rng: ChaCha20"),
+            (_, None, _) => "rng: ThreadRng (unseeded)".to_owned(),
+        }
+    }
+
+    /// Returns the random number generator used by the commands.
+    fn rng(&self) -> Box<dyn RngCore> {
+        if self.secure {
+            Box::new(OsRng)
+        } else if let Some(seed) = self.seed {
+            let seed = match self.stream {
+                Some(stream) => seed ^ mix(stream),
+                None => seed,
+            };
+            Box::new(ChaCha20Rng::seed_from_u64(seed))
+        } else {
+            Box::new(rand::thread_rng())
+        }
+    }
+
+    /// Returns the options that change how the commands run.
+    fn run_options(&self) -> RunOptions {
+        // the seed for shuffling ties is derived from the seed instead of being
+        // drawn from the generator, so shuffling ties doesn't change the output
+        let tie_seed = match (self.secure, self.seed) {
+            (false, Some(seed)) => mix(mix(seed) ^ self.stream.unwrap_or(0)),
+            _ => rand::random(),
+        };
+
+        RunOptions {
+            ascii_only: self.ascii_only,
+            max_amount: self.max_amount,
+            dry_run: self.dry_run,
+            tie_seed,
+        }
+    }
+
+    /// Returns the format of the output, taking the JSON flags into account.
+    ///
+    /// [`Format::Auto`] is resolved to [`Format::Pretty`] if the output is written to
+    /// a terminal with colors enabled, and to [`Format::Plain`] otherwise.
+    fn format(&self) -> Format {
+        if self.json {
+            Format::JsonPretty
+        } else if self.json_compact {
+            Format::Json
+        } else if self.format == Format::Auto {
+            let color = color_choice(self.no_color, stdout().is_terminal());
+            if self.output_file.is_none() && color != ColorChoice::Never {
+                Format::Pretty
+            } else {
+                Format::Plain
+            }
+        } else {
+            self.format
+        }
+    }
+}
+
+/// Asks on the `stderr` whether the file at `path` should be overwritten.
+///
+/// Returns an error if the answer isn't yes or the `stdin` is not a terminal.
+fn confirm_overwrite(path: &Path) -> Result<()> {
+    if !stdin().is_terminal() {
+        return Err(
+            format!("`{}` already exists, use `--force` to overwrite it", path.display()).into()
+        );
+    }
+
+    eprint!("overwrite `{}`? [y/N] ", path.display());
+    stderr().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(format!("not overwriting `{}`", path.display()).into()),
+    }
+}
+
+/// Asks on the `stderr` for the weight of each item until a valid weight or an
+/// empty answer, which is a weight of 1, is read.
+///
+/// Returns an error if the `stdin` is not a terminal.
+fn prompt_weights(items: &[String]) -> Result<Vec<f64>> {
+    if !stdin().is_terminal() {
+        return Err("`interactive-weights` requires the `stdin` to be a terminal".into());
+    }
+
+    let mut weights = Vec::with_capacity(items.len());
+    for item in items {
+        loop {
+            eprint!("weight of `{item}` [1]: ");
+            stderr().flush()?;
+            let mut answer = String::new();
+            if stdin().read_line(&mut answer)? == 0 {
+                return Err(format!("no weight was entered for `{item}`").into());
+            }
+
+            let answer = answer.trim();
+            let weight = if answer.is_empty() { Ok(1.0) } else { answer.parse::<f64>() };
+            match weight {
+                Ok(w) if w >= 0.0 && w.is_finite() => {
+                    weights.push(w);
+                    break;
+                },
+                _ => warn(format_args!("`{answer}` is not a non-negative number")),
+            }
+        }
+    }
+
+    Ok(weights)
+}
+
+/// Parses a decimal seed, or a hexadecimal seed prefixed with `0x`.
+fn parse_seed(s: &str) -> std::result::Result<u64, String> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Parses a number that is at least 1.
+fn parse_positive(s: &str) -> std::result::Result<usize, String> {
+    match s.parse::<usize>().map_err(|e| e.to_string())? {
+        0 => Err("must be at least 1".to_owned()),
+        n => Ok(n),
+    }
+}
+
+/// Parses a non-negative percentage, with or without a trailing `%`.
+fn parse_percent(s: &str) -> std::result::Result<f64, String> {
+    match s.strip_suffix('%').unwrap_or(s).parse::<f64>() {
+        Ok(p) if p >= 0.0 && p.is_finite() => Ok(p),
+        _ => Err(format!("invalid percentage `{s}`")),
+    }
+}
+
+/// Scrambles the bits of `n` (the SplitMix64 finalizer).
+///
+/// Nearby inputs produce very different outputs, which makes the result suitable
+/// for deriving seeds.
+fn mix(mut n: u64) -> u64 {
+    n = n.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    n = (n ^ (n >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    n = (n ^ (n >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    n ^ (n >> 31)
+}
+
+/// A value of the running process mixed into the seed by `--seed-entropy`.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum SeedEntropy {
+    /// Don't change the seed.
+    None,
+    /// The ID of the process.
+    Pid,
+    /// The name of the machine.
+    Hostname,
+    /// The current time in nanoseconds.
+    Time,
+}
+
+impl SeedEntropy {
+    /// Returns the value of the source, which is 0 for [`SeedEntropy::None`].
+    fn value(self) -> Result<u64> {
+        Ok(match self {
+            Self::None => 0,
+            Self::Pid => u64::from(std::process::id()),
+            Self::Hostname => {
+                let hostname = fs::read_to_string("/etc/hostname")
+                    .ok()
+                    .or_else(|| env::var("HOSTNAME").ok())
+                    .or_else(|| env::var("COMPUTERNAME").ok())
+                    .ok_or("failed to get the hostname")?;
+                hostname
+                    .trim()
+                    .bytes()
+                    .fold(0, |h, b| mix(h ^ u64::from(b)))
+            },
+            Self::Time => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -45,6 +443,9 @@ enum Command {
         /// The number of times to flip a coin.
         #[clap(default_value_t = 1)]
         amount: usize,
+        /// Print nothing if `amount` is zero instead of returning an error.
+        #[clap(long)]
+        allow_empty: bool,
         /// Show the number of times heads and tails were selected.
         #[clap(short, long)]
         count: bool,
@@ -52,9 +453,40 @@ enum Command {
         ///
         /// This is enabled by default (up to a max threshold), but using the
         /// `count` flag disables it. Explicitly passing this flag enables it even
-        /// with the `count` flag.
+        /// with the `count` flag, which shows the results first, then one blank
+        /// line and then the count.
         #[clap(short = 'A', long)]
         all: bool,
+        /// Options for displaying the number of times each item was selected.
+        #[clap(flatten)]
+        count_options: CountOptions,
+        /// Show the longest run of consecutive heads and tails.
+        #[clap(long)]
+        streaks: bool,
+        /// Show the proportion of heads after 10, 100, 1000, ... flips and after
+        /// the last flip instead of the flips.
+        #[clap(long, conflicts_with_all = &["count", "all", "streaks"])]
+        converge: bool,
+        /// Flip until a side wins the majority of `N` flips, then show the
+        /// flips and the winner instead.
+        ///
+        /// `N` must be odd, and `amount` is ignored.
+        #[clap(
+            long,
+            value_name = "N",
+            conflicts_with_all = &["count", "all", "streaks", "converge"]
+        )]
+        best_of: Option<usize>,
+        /// Also write the proportion of heads after every flip to a CSV file of
+        /// `flip,proportion` rows.
+        ///
+        /// The rows are written while flipping, so the flips aren't kept in memory.
+        #[clap(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = &["streaks", "converge", "best-of"]
+        )]
+        trace_file: Option<PathBuf>,
     },
     /// Choose `amount` elements from a list of items.
     ///
@@ -63,14 +495,92 @@ enum Command {
     Choose {
         /// The items to choose from.
         items: Vec<String>,
+        /// Choose from the integers of an inclusive range instead of `items`, like
+        /// `1:10` or `10:0:-2`.
+        #[clap(
+            long,
+            value_name = "START:END[:STEP]",
+            allow_hyphen_values = true,
+            conflicts_with_all = &["items", "empirical"]
+        )]
+        range: Option<ItemRange>,
         /// The number of items to choose.
-        #[clap(short, long, default_value_t = 1, short_alias = 'n')]
-        amount: usize,
-        /// The list of comma-separated weights of the items.
         ///
-        /// The number of weights must be equal to the number of items.
-        #[clap(short, long, use_value_delimiter = true)]
-        weights: Vec<f64>,
+        /// A percentage like `20%` chooses that share of the items, rounded and
+        /// at least 1. Percentages above 100% choose every item once, unless
+        /// `repetition` is used.
+        #[clap(short, long, default_value = "1", short_alias = 'n')]
+        amount: Amount,
+        /// Print nothing if `amount` is zero instead of returning an error.
+        #[clap(long)]
+        allow_empty: bool,
+        /// The list of weights of the items, separated by `weights-delimiter`.
+        ///
+        /// The number of weights must be equal to the number of items, unless
+        /// `pad-weights` is used. Weights can be fractions like `1/3,1/3,1/3`, or
+        /// a ratio like `2:1:1`.
+        #[clap(short, long)]
+        weights: Option<String>,
+        /// The character separating the weights.
+        #[clap(long, default_value_t = ',', value_name = "CHAR")]
+        weights_delimiter: char,
+        /// Give a weight of 1 to the items without a weight, and ignore the extra
+        /// weights with a warning.
+        #[clap(long, requires = "weights")]
+        pad_weights: bool,
+        /// Give every item the same weight, ignoring the weights from the other
+        /// options.
+        ///
+        /// This makes it easy to compare a weighted choice to a uniform one.
+        /// The items of `empirical` are still used.
+        #[clap(long)]
+        uniform: bool,
+        /// Ask for the weight of each item on the `stderr` and read it from the
+        /// `stdin`.
+        ///
+        /// An empty answer is a weight of 1. Returns an error if the `stdin` is
+        /// not a terminal.
+        #[clap(
+            long,
+            conflicts_with_all = &["weights", "weight-by-frequency", "empirical"]
+        )]
+        interactive_weights: bool,
+        /// Interpret the weights as a cumulative distribution.
+        ///
+        /// The weights must be non-decreasing, and the weight of each item is
+        /// the difference from the previous weight.
+        #[clap(long, requires = "weights")]
+        cumulative_weights: bool,
+        /// Interpret the weights on a log scale, so a weight `w` becomes `10^w`.
+        #[clap(long, requires = "weights", conflicts_with = "cumulative-weights")]
+        log_weights: bool,
+        /// Use the number of times each item is repeated in the list as its weight.
+        ///
+        /// Repeated items are only chosen once without repetition.
+        #[clap(long, conflicts_with = "weights")]
+        weight_by_frequency: bool,
+        /// Read the items and their weights from a CSV file of `label,weight` rows.
+        #[clap(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = &["items", "weights", "weight-by-frequency"]
+        )]
+        empirical: Option<PathBuf>,
+        /// Transform the weights with the softmax function before choosing.
+        ///
+        /// This lets you use raw scores (like logits) as weights.
+        #[clap(long)]
+        softmax: bool,
+        /// The temperature of the softmax function.
+        ///
+        /// Higher temperatures make the choice more uniform, lower temperatures
+        /// favor the items with the largest weights.
+        #[clap(long, default_value_t = 1.0, requires = "softmax")]
+        temperature: f64,
+        /// Raise every weight below this floor up to it, so no item has a zero
+        /// probability.
+        #[clap(long, value_name = "WEIGHT", allow_hyphen_values = true)]
+        min_weight: Option<f64>,
         /// Show the number of times each item was selected.
         #[clap(short, long)]
         count: bool,
@@ -78,32 +588,117 @@ enum Command {
         ///
         /// This is enabled by default (up to a max threshold), but using the
         /// `count` flag disables it. Explicitly passing this flag enables it even
-        /// with the `count` flag.
+        /// with the `count` flag, which shows the results first, then one blank
+        /// line and then the count.
         #[clap(short = 'A', long)]
         all: bool,
+        /// Options for displaying the number of times each item was selected.
+        #[clap(flatten)]
+        count_options: CountOptions,
         /// Choose items with repetition.
         #[clap(short, long)]
         repetition: bool,
+        /// The algorithm used to choose weighted items without repetition.
+        #[clap(short, long, default_value_t = Method::Efraimidis, value_enum)]
+        method: Method,
+        /// Choose items with repetition using an alias table.
+        ///
+        /// Building the table takes longer, but every choice then takes the
+        /// same time however many items there are, which is faster for large
+        /// amounts of many weighted items.
+        #[clap(long, requires = "repetition")]
+        alias_method: bool,
+        /// The order in which the items chosen without repetition are shown.
+        ///
+        /// This doesn't change which items are chosen.
+        #[clap(long, default_value_t = Order::Sampled, value_enum, conflicts_with = "repetition")]
+        order: Order,
+        /// Show the index of each chosen item in the list of items.
+        #[clap(long)]
+        show_indices: bool,
+        /// Print the probability of choosing each item on the `stderr` before choosing.
+        #[clap(long)]
+        show_weights: bool,
+        /// Print every item in a random order, numbered by rank.
+        ///
+        /// Items with larger weights are more likely to be ranked higher. The
+        /// `amount` is ignored.
+        #[clap(long)]
+        rank: bool,
+        /// Exit with an error if the observed proportion of any item differs
+        /// from its expected proportion by more than this many percentage points.
+        ///
+        /// The failing items are listed in the error. The expected proportions
+        /// are the normalized weights, so this requires choosing with repetition,
+        /// either with `repetition` or an `amount` larger than the number of items.
+        #[clap(long, value_name = "PERCENT", value_parser = parse_percent, conflicts_with = "rank")]
+        assert_within: Option<f64>,
+        /// Show the items that weren't chosen instead, in their original order.
+        ///
+        /// Items with larger weights are more likely to be left out.
+        #[clap(long, conflicts_with_all = &["repetition", "rank", "count", "all", "assert-within"])]
+        invert: bool,
+        /// Show the `amount` items with the largest weights instead of choosing
+        /// randomly.
+        ///
+        /// This is not random: items with equal weights are shown in their
+        /// original order.
+        #[clap(
+            long,
+            conflicts_with_all = &["repetition", "rank", "count", "all", "assert-within", "invert"]
+        )]
+        top_weighted: bool,
     },
     /// Shuffle a list of items.
     #[clap(alias = "shfl")]
     Shuffle {
         /// The items to shuffle.
         items: Vec<String>,
+        /// Only shuffle the first `K` items, keeping the rest in order.
+        ///
+        /// A percentage like `20%` shuffles that share of the items, rounded and
+        /// at least 1.
+        #[clap(long, value_name = "K")]
+        partial: Option<Amount>,
+        /// Shuffle consecutive groups of this many items as blocks.
+        ///
+        /// If the number of items isn't divisible by the size, the last group is
+        /// smaller.
+        #[clap(long, value_name = "N", conflicts_with_all = &["partial", "trials"])]
+        chunk_size: Option<usize>,
+        /// Shuffle the items this many times and count the items that land at `position`.
+        #[clap(long, value_name = "N", requires = "position")]
+        trials: Option<usize>,
+        /// The zero-based position whose items are counted over the trials.
+        #[clap(long, value_name = "I", requires = "trials")]
+        position: Option<usize>,
+        /// Options for displaying the number of times each item landed at `position`.
+        #[clap(flatten)]
+        count_options: CountOptions,
     },
     /// Print a random number between 0.0 and 1.0 (not inclusive).
     ///
-    /// You can optionally provide a lower and upper bound.
-    #[clap(alias = "rand")]
+    /// You can optionally provide a lower and upper bound. With only one bound
+    /// `N`, the range is from 0 to `N`, or from `N` to 0 if `N` is negative.
+    #[clap(alias = "rand", allow_negative_numbers = true)]
     Random {
         /// Include the upper bound.
         ///
         /// It is not included by default.
         #[clap(short, long)]
         inclusive: bool,
-        /// The precision of a floating point number.
-        #[clap(short, long, default_value_t = 6)]
-        precision: usize,
+        /// The number of random numbers to generate.
+        #[clap(short, short_alias = 'n', long, default_value_t = 1)]
+        amount: usize,
+        /// Options for summarizing the generated numbers.
+        #[clap(flatten)]
+        summary: Summary,
+        /// Options for formatting a floating point number.
+        #[clap(flatten)]
+        float_format: FloatFormat,
+        /// Print only the numbers, one per line and without colors.
+        #[clap(long, conflicts_with_all = &["min", "max"])]
+        raw: bool,
         /// The lower bound of the range.
         start: Option<Num>,
         /// The upper bound of the range.
@@ -120,6 +715,26 @@ enum Command {
         /// The case of the string.
         #[clap(short, long, default_value_t = Case::Lower, value_enum)]
         case: Case,
+        /// The text added before the string.
+        ///
+        /// It is not counted towards the length of the string.
+        #[clap(long, default_value = "", allow_hyphen_values = true)]
+        prefix: String,
+        /// The text added after the string.
+        ///
+        /// It is not counted towards the length of the string.
+        #[clap(long, default_value = "", allow_hyphen_values = true)]
+        suffix: String,
+        /// Generate a string of syllables that can be read aloud, like "bitako".
+        ///
+        /// The string is made of whole syllables, so it can be up to two
+        /// characters longer than `length`.
+        #[clap(long)]
+        pronounceable: bool,
+        /// Leave out characters that are easily confused with each other, like
+        /// `0` and `O` or `1`, `l` and `I`.
+        #[clap(long, conflicts_with = "pronounceable")]
+        no_ambiguous: bool,
     },
     /// Rolls a n-sided die.
     ///
@@ -132,6 +747,14 @@ enum Command {
         /// The number of times to roll the die.
         #[clap(short, short_alias = 'n', long, default_value_t = 1)]
         times: usize,
+        /// Print nothing if `times` is zero instead of returning an error.
+        #[clap(long)]
+        allow_empty: bool,
+        /// The comma-separated labels of the faces of the die.
+        ///
+        /// If provided, the die has one face for each label and `sides` is ignored.
+        #[clap(long, use_value_delimiter = true, allow_hyphen_values = true)]
+        faces: Vec<String>,
         /// Show the number of times each number was rolled.
         #[clap(short, long)]
         count: bool,
@@ -139,9 +762,39 @@ enum Command {
         ///
         /// This is enabled by default (up to a max threshold), but using the
         /// `count` flag disables it. Explicitly passing this flag enables it even
-        /// with the `count` flag.
+        /// with the `count` flag, which shows the results first, then one blank
+        /// line and then the count.
         #[clap(short = 'A', long)]
         all: bool,
+        /// Print the running count on the `stderr` every `N` rolls.
+        #[clap(long, value_name = "N")]
+        count_interval: Option<usize>,
+        /// Show the sum of all the rolls.
+        ///
+        /// If `faces` is provided, every face must be an integer. The sum is also
+        /// shown when only the count is.
+        #[clap(long, alias = "weighted-sum")]
+        total: bool,
+        /// The number added to the sum of the rolls.
+        #[clap(long, default_value_t = 0, allow_hyphen_values = true, requires = "total")]
+        modifier: i128,
+        /// Include the faces that were never rolled in the count.
+        #[clap(long)]
+        show_zero: bool,
+        /// Only keep the `K` highest rolls.
+        #[clap(long, value_name = "K", conflicts_with_all = &["faces", "keep-lowest"])]
+        keep_highest: Option<usize>,
+        /// Only keep the `K` lowest rolls.
+        #[clap(long, value_name = "K", conflicts_with = "faces")]
+        keep_lowest: Option<usize>,
+        /// Options for displaying the number of times each item was selected.
+        #[clap(flatten)]
+        count_options: CountOptions,
+        /// Print only the rolls, one per line and without colors.
+        ///
+        /// Every roll is printed, however many there are.
+        #[clap(long, conflicts_with_all = &["count", "all", "total"])]
+        raw: bool,
     },
     /// Assigns items from one list to another randomly.
     ///
@@ -154,14 +807,426 @@ enum Command {
         /// The items on the right side of the assignment.
         #[clap(short, long, use_value_delimiter = true)]
         right: Vec<String>,
+        /// Return an error if an item appears more than once on the left side.
+        ///
+        /// Otherwise, structured formats only keep the last assignment of a
+        /// repeated item.
+        #[clap(long)]
+        strict: bool,
+        /// The comma-separated weights of the items on the right side.
+        ///
+        /// Items with larger weights are more likely to be assigned to the items
+        /// at the start of the left side.
+        #[clap(short, long)]
+        weights: Option<String>,
+        /// Assign the items this many times and count how often each pair occurs.
+        #[clap(long, value_name = "N", requires = "count")]
+        trials: Option<usize>,
+        /// Show the number of times each `left:right` pair occurred over the trials.
+        #[clap(short, long, requires = "trials")]
+        count: bool,
+        /// Options for displaying the number of times each pair occurred.
+        #[clap(flatten)]
+        count_options: CountOptions,
+    },
+    /// Randomly interleave two lists, keeping the order of the items within each list.
+    ///
+    /// Every interleaving of the two lists is equally likely.
+    Merge {
+        /// The items of the first list.
+        #[clap(short, long, use_value_delimiter = true)]
+        left: Vec<String>,
+        /// The items of the second list.
+        #[clap(short, long, use_value_delimiter = true)]
+        right: Vec<String>,
+    },
+    /// Choose `amount` random lines from the standard input.
+    ///
+    /// Only `amount` lines are kept in memory at a time, so this works on streams
+    /// of any size.
+    #[clap(alias = "res")]
+    Reservoir {
+        /// The number of lines to choose.
+        #[clap(short, short_alias = 'n', long, default_value_t = 1)]
+        amount: usize,
+    },
+    /// Roll on a random table and print the matching outcome.
+    ///
+    /// Each entry maps a range of rolls to an outcome, like `1-50:common`. The
+    /// ranges must be contiguous and must not overlap.
+    Table {
+        /// The entries of the table, in the form `start-end:outcome` or `roll:outcome`.
+        #[clap(required = true)]
+        entries: Vec<TableEntry>,
+    },
+    /// Draw `amount` integers from an inclusive range.
+    ///
+    /// The integers are drawn without replacement (no duplicates) unless the
+    /// `replacement` flag is passed.
+    #[clap(alias = "sample", allow_negative_numbers = true)]
+    Draw {
+        /// The lower bound of the range.
+        start: i64,
+        /// The upper bound of the range (inclusive).
+        end: i64,
+        /// The number of integers to draw.
+        #[clap(short, short_alias = 'n', long, default_value_t = 1)]
+        amount: usize,
+        /// Draw every integer independently, allowing duplicates.
+        #[clap(short, long)]
+        replacement: bool,
+        /// The smallest difference between any two drawn integers.
+        ///
+        /// If this is larger than 1, the integers are printed in increasing order.
+        #[clap(long, value_name = "GAP", conflicts_with = "replacement")]
+        min_gap: Option<usize>,
+    },
+    /// Print example invocations of the commands.
+    Examples,
+    /// Roll the dice expressions read from the `stdin`, like `2d6` or `1d20+5`.
+    ///
+    /// The total of each line is printed as soon as the line is read. Empty
+    /// lines are skipped, and invalid lines are skipped with a warning.
+    Eval,
+    /// Run a command again from the parameters printed by `--emit-params`.
+    ///
+    /// The output is written to the `stdout`, since an `--output-file` in the
+    /// parameters is the file they were written to.
+    Replay {
+        /// The file containing the parameters.
+        file: PathBuf,
+    },
+    /// Run a command `iterations` times and report how long it took.
+    ///
+    /// The output of the command is discarded and the timing is printed on the
+    /// `stderr`.
+    #[clap(hide = true, trailing_var_arg = true)]
+    Benchmark {
+        /// The number of times to run the command.
+        #[clap(short, short_alias = 'n', long, default_value_t = 1000)]
+        iterations: u32,
+        /// The command to benchmark, along with its arguments.
+        #[clap(required = true)]
+        command: Vec<String>,
     },
 }
 
+/// Options for displaying the number of times each item was selected.
+#[derive(Debug, Clone, Args)]
+struct CountOptions {
+    /// Only show the `top` most frequent items in the count.
+    ///
+    /// If items are left out, structured formats nest the count under a `counts`
+    /// key next to the number of `omitted` items.
+    #[clap(long, value_name = "N", value_parser = parse_positive)]
+    top: Option<usize>,
+    /// Show the total number of selections after the count.
+    ///
+    /// Structured formats nest the count under a `counts` key next to the `total`.
+    #[clap(long)]
+    count_total: bool,
+    /// The template of each line of the count.
+    ///
+    /// The `{item}`, `{count}` and `{percent}` placeholders are replaced with the
+    /// item, the number of times it was selected and the percentage of selections
+    /// it makes up. Use `{{` and `}}` for literal braces.
+    #[clap(long, value_name = "TEMPLATE")]
+    count_format: Option<CountFormat>,
+    /// How the count is sorted.
+    #[clap(long, default_value_t = Sort::Count, value_enum)]
+    sort: Sort,
+    /// Reverse the order of the count.
+    ///
+    /// This flips the active sort: the least frequent items are shown first when
+    /// sorting by count, and labels are sorted in reverse order when sorting by label.
+    #[clap(long)]
+    descending: bool,
+    /// Draw a live-updating histogram of the count on the `stderr` while selecting.
+    ///
+    /// The histogram is only drawn if the `stderr` is a terminal.
+    #[clap(long)]
+    live_count: bool,
+    /// Print the Shannon entropy of the selections in bits on the `stderr`.
+    #[clap(long)]
+    entropy: bool,
+    /// Count the items by the part of their labels before the first `SEP`.
+    ///
+    /// Labels without `SEP` are counted as a whole.
+    #[clap(long, value_name = "SEP")]
+    group_by: Option<String>,
+    /// Also write every item and its count to a CSV file of `item,count` rows.
+    #[clap(long, value_name = "FILE")]
+    count_file: Option<PathBuf>,
+    /// Order items with equal counts randomly instead of by label.
+    ///
+    /// The order is reproducible with `--seed`.
+    #[clap(long)]
+    shuffle_ties: bool,
+    /// Lay out the count in `N` aligned columns.
+    #[clap(long, value_name = "N")]
+    count_columns: Option<usize>,
+    /// Show the percentage of each item in the count and how far it is from the
+    /// expected percentage.
+    ///
+    /// The expected percentages come from the weights of `choose`, and are
+    /// uniform for `coin` and `die`.
+    #[clap(long)]
+    deviation: bool,
+    /// Write the count as an array of `{"item": .., "count": ..}` objects in
+    /// the sorted order, instead of an object that loses the order.
+    #[clap(long)]
+    json_ordered: bool,
+}
+
+impl CountOptions {
+    /// Sorts the `counts` in the requested order.
+    ///
+    /// If `tie_seed` is provided, items with equal counts are shuffled with it
+    /// when sorting by count.
+    fn sorted<T: Ord>(
+        &self,
+        counts: impl IntoIterator<Item = (T, u64)>,
+        tie_seed: Option<u64>,
+    ) -> Vec<(T, u64)> {
+        let mut sorted = counts
+            .into_iter()
+            .sorted_by(|(a, a_count), (b, b_count)| {
+                let ordering = match self.sort {
+                    Sort::Count => b_count.cmp(a_count).then_with(|| a.cmp(b)),
+                    Sort::Label => a.cmp(b),
+                };
+                if self.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if let (Some(seed), Sort::Count) = (tie_seed, self.sort) {
+            // the seed is derived from `--seed`, so the order is reproducible with
+            // the same seed
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            for ties in sorted.chunk_by_mut(|(_, a), (_, b)| a == b) {
+                ties.shuffle(&mut rng);
+            }
+        }
+
+        sorted
+    }
+}
+
+/// The order in which the count is shown.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Sort {
+    /// Show the most frequent items first, breaking ties by label.
+    Count,
+    /// Show the items in order of their labels.
+    Label,
+}
+
+/// How the selections of a command are displayed.
+#[derive(Debug, Clone, Copy)]
+struct SelectionDisplay<'a> {
+    /// Show the number of times each item was selected.
+    count: bool,
+    /// Show every selection in order.
+    all: bool,
+    /// Options for displaying the count.
+    options: &'a CountOptions,
+    /// The seed used to shuffle items with equal counts, if they are shuffled.
+    tie_seed: Option<u64>,
+    /// The proportions the observed counts are checked against, if any.
+    expectation: Option<&'a Expectation>,
+}
+
+/// The expected proportion of each item and how far the observed proportions may
+/// deviate from it.
+#[derive(Debug)]
+struct Expectation {
+    /// The expected proportion of each distinct item, in `0.0..=1.0`.
+    proportions: Vec<(String, f64)>,
+    /// The maximum deviation, in percentage points, if it is checked.
+    tolerance: Option<f64>,
+}
+
+impl Expectation {
+    /// Builds the expectation of choosing `items` with `weights`.
+    ///
+    /// The proportions of repeated items are added together. Returns `None` if
+    /// the weights can't be normalized.
+    fn new(items: &[String], weights: &[f64], tolerance: Option<f64>) -> Option<Self> {
+        if items.len() != weights.len() {
+            return None;
+        }
+
+        let mut proportions: Vec<(String, f64)> = Vec::new();
+        for (item, p) in items.iter().zip(weights::normalize(weights)?) {
+            match proportions.iter_mut().find(|(s, _)| s == item) {
+                Some((_, total)) => *total += p,
+                None => proportions.push((item.clone(), p)),
+            }
+        }
+
+        Some(Self {
+            proportions,
+            tolerance,
+        })
+    }
+
+    /// Returns an error listing every item whose observed proportion deviates
+    /// from the expected one by more than the tolerance, if there is one.
+    fn check<D: Display>(&self, counts: &HashMap<D, u64>) -> Result<()> {
+        let Some(tolerance) = self.tolerance else {
+            return Ok(());
+        };
+
+        let total = counts.values().sum::<u64>() as f64;
+        let mut observed = HashMap::new();
+        for (item, count) in counts {
+            *observed.entry(item.to_string()).or_insert(0) += count;
+        }
+
+        let failures = self
+            .proportions
+            .iter()
+            .filter_map(|(item, expected)| {
+                let actual = observed.get(item).copied().unwrap_or(0) as f64 / total;
+                ((actual - expected).abs() * 100.0 > tolerance).then(|| {
+                    format!(
+                        "{item} (observed {:.2}%, expected {:.2}%)",
+                        actual * 100.0,
+                        expected * 100.0
+                    )
+                })
+            })
+            .join(", ");
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("observed proportions deviate by more than {tolerance}%: {failures}")
+                .into())
+        }
+    }
+}
+
+impl<'a> SelectionDisplay<'a> {
+    /// Resolves the `count` and `all` flags of a command that selects `amount` items.
+    ///
+    /// Every selection is shown if `all` is set or `amount` is at most the
+    /// threshold. The count is shown if `count` is set or not every selection is
+    /// shown.
+    ///
+    /// Ties are shuffled with `tie_seed` if they are shuffled.
+    fn new(
+        tie_seed: u64,
+        count: bool,
+        all: bool,
+        amount: usize,
+        options: &'a CountOptions,
+    ) -> Self {
+        let all = all || amount <= AMOUNT_THRESHOLD;
+        let count = count || !all;
+
+        Self {
+            count,
+            all,
+            options,
+            tie_seed: options.shuffle_ties.then_some(tie_seed),
+            expectation: None,
+        }
+    }
+
+    /// Shows only the count, for commands whose selections aren't useful on
+    /// their own.
+    fn count_only(tie_seed: u64, options: &'a CountOptions) -> Self {
+        Self {
+            count: true,
+            all: false,
+            options,
+            tie_seed: options.shuffle_ties.then_some(tie_seed),
+            expectation: None,
+        }
+    }
+}
+
+impl Command {
+    /// Returns the number of values the command generates, if it has an amount.
+    fn amount(&self) -> Option<usize> {
+        match self {
+            Self::Coin {
+                amount, ..
+            }
+            | Self::Random {
+                amount, ..
+            }
+            | Self::Reservoir {
+                amount, ..
+            }
+            | Self::Draw {
+                amount, ..
+            } => Some(*amount),
+            Self::Choose {
+                amount: Amount::Count(amount), ..
+            } => Some(*amount),
+            Self::String {
+                length, ..
+            } => Some(*length),
+            Self::Die {
+                times, ..
+            } => Some(*times),
+            Self::Shuffle {
+                trials, ..
+            }
+            | Self::Assign {
+                trials, ..
+            } => *trials,
+            _ => None,
+        }
+    }
+
+    /// Returns an error if the command generates more than `max` values.
+    ///
+    /// Amounts that depend on the number of items are checked once they are known.
+    fn check_amount(&self, max: usize) -> Result<()> {
+        match self.amount() {
+            Some(amount) => check_max_amount(amount, max),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns an error if `amount` is larger than `max`.
+fn check_max_amount(amount: usize, max: usize) -> Result<()> {
+    if amount > max {
+        Err(format!(
+            "the amount {amount} is larger than the maximum of {max}, use `--max-amount` to allow \
+             it"
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
 impl Default for Command {
     fn default() -> Self {
         Self::Random {
             inclusive: false,
-            precision: 2,
+            amount: 1,
+            summary: Summary {
+                min: false,
+                max: false,
+                buckets: None,
+            },
+            float_format: FloatFormat {
+                precision: 2,
+                sig_figs: None,
+                decimal_comma: false,
+                rounding: Rounding::HalfEven,
+            },
+            raw: false,
             start: Some(Num::FLOAT_0),
             end: Some(Num::FLOAT_1),
         }
@@ -187,264 +1252,2228 @@ impl Num {
     }
 }
 
+impl From<i128> for Num {
+    fn from(i: i128) -> Self {
+        Self::Int(i)
+    }
+}
+
+impl From<f64> for Num {
+    fn from(f: f64) -> Self {
+        Self::Float(f)
+    }
+}
+
 impl FromStr for Num {
     type Err = String;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         s.parse::<i128>()
             .map(Num::Int)
-            .or_else(|_| (s.parse::<f64>().map(Num::Float)))
+            .or_else(|_| s.parse::<f64>().map(Num::Float))
             .map_err(|e| e.to_string())
     }
 }
 
-impl Display for Num {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// A number of items, either exact or as a percentage of all the items.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Amount {
+    /// An exact number of items.
+    Count(usize),
+    /// A percentage of the items.
+    Percent(f64),
+}
+
+impl Amount {
+    /// Returns the number of items out of `len` items.
+    ///
+    /// Percentages are rounded and are at least 1.
+    fn of(self, len: usize) -> usize {
         match self {
-            Num::Int(i) => i.fmt(fmt),
-            Num::Float(f) => f.fmt(fmt),
+            Self::Count(n) => n,
+            Self::Percent(p) => ((len as f64 * p / 100.0).round() as usize).max(1),
         }
     }
 }
 
-#[derive(ValueEnum, Debug, Clone, Copy)]
-enum Case {
-    Lower,
-    Upper,
-    Mixed,
-}
+impl FromStr for Amount {
+    type Err = String;
 
-fn random_cmd<T: PartialOrd + SampleUniform + Display>(
-    lower: T,
-    upper: T,
-    inclusive: bool,
-    precision: usize,
-) -> Result<()> {
-    if lower >= upper {
-        return Err("lower bound should be smaller than upper".into());
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(p) => match p.parse::<f64>() {
+                Ok(p) if p >= 0.0 && p.is_finite() => Ok(Self::Percent(p)),
+                _ => Err(format!("invalid percentage `{s}`")),
+            },
+            None => s.parse().map(Self::Count).map_err(|e| e.to_string()),
+        }
     }
+}
 
-    let mut rng = rand::thread_rng();
-
-    let num = if inclusive { rng.gen_range(lower..=upper) } else { rng.gen_range(lower..upper) };
+/// An inclusive range of integers with a step, used as a list of items.
+#[derive(Debug, Copy, Clone)]
+struct ItemRange {
+    /// The first integer.
+    start: i64,
+    /// The last integer, if it is reached by the steps.
+    end: i64,
+    /// The difference between two consecutive integers.
+    step: i64,
+}
 
-    println!("{num:.precision$}");
+impl ItemRange {
+    /// Returns the number of integers in the range, or `None` if it doesn't fit
+    /// in a `u64`.
+    fn len(self) -> Option<u64> {
+        (self.start.abs_diff(self.end) / self.step.unsigned_abs()).checked_add(1)
+    }
 
-    Ok(())
+    /// Returns the integers of the range as items.
+    fn items(self) -> Vec<String> {
+        let step = self.step.unsigned_abs() as usize;
+        if self.step > 0 {
+            (self.start..=self.end)
+                .step_by(step)
+                .map(|i| i.to_string())
+                .collect()
+        } else {
+            (self.end..=self.start)
+                .rev()
+                .step_by(step)
+                .map(|i| i.to_string())
+                .collect()
+        }
+    }
 }
 
-fn shuffle_cmd(items: &mut [String]) {
-    let mut rng = rand::thread_rng();
-    items.shuffle(&mut rng);
-    println!("{}", items.iter().join(", "));
-}
+impl FromStr for ItemRange {
+    type Err = String;
 
-fn choose_with_repetition<S: Display + Eq + Hash>(
-    items: Vec<S>,
-    weights: Vec<f64>,
-    amount: usize,
-    count: bool,
-    all: bool,
-) -> Result<()> {
-    let dist = WeightedIndex::new(weights)?;
-    let mut rng = rand::thread_rng();
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parts = s
+            .split(':')
+            .map(|part| part.trim().parse::<i64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid range `{s}`: {e}"))?;
 
-    let selections = (0..amount).map(|_| &items[dist.sample(&mut rng)]);
-    print_selections(selections, count, all, amount);
+        let (start, end, step) = match parts[..] {
+            [start, end] => (start, end, 1),
+            [start, end, step] => (start, end, step),
+            _ => return Err(format!("invalid range `{s}`, expected `START:END[:STEP]`")),
+        };
 
-    Ok(())
+        if step == 0 {
+            return Err("the step of a range must not be zero".to_owned());
+        }
+        if (step > 0 && start > end) || (step < 0 && start < end) {
+            return Err(format!("the range `{s}` is empty"));
+        }
+
+        Ok(Self {
+            start,
+            end,
+            step,
+        })
+    }
 }
 
-fn choose_without_repetition(
+/// Returns the range of the `random` command from the bounds that were provided.
+///
+/// - No bounds: `0.0..1.0`.
+/// - Only a non-negative `n`: `0..n`.
+/// - Only a negative `n`: `n..0`.
+/// - Both bounds: `start..end`.
+///
+/// The inferred bound is an integer, so the range is only a float range if `n`
+/// is a float.
+fn infer_range(start: Option<Num>, end: Option<Num>) -> (Num, Num) {
+    match (start, end) {
+        (None, end) => (Num::FLOAT_0, end.unwrap_or(Num::FLOAT_1)),
+        (Some(n), None) if n.as_float() < 0.0 => (n, Num::INT_0),
+        (Some(n), None) => (Num::INT_0, n),
+        (Some(start), Some(end)) => (start, end),
+    }
+}
+
+impl Display for Num {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Num::Int(i) => i.fmt(fmt),
+            Num::Float(f) => f.fmt(fmt),
+        }
+    }
+}
+
+/// An entry of a random table.
+#[derive(Debug, Clone)]
+struct TableEntry {
+    start: u64,
+    end: u64,
+    outcome: String,
+}
+
+impl FromStr for TableEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (range, outcome) = s
+            .split_once(':')
+            .ok_or_else(|| "expected an entry like `1-50:outcome`".to_owned())?;
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let start = start.trim().parse::<u64>().map_err(|e| e.to_string())?;
+        let end = end.trim().parse::<u64>().map_err(|e| e.to_string())?;
+
+        if start > end {
+            return Err(format!("range `{range}` ends before it starts"));
+        }
+
+        Ok(Self {
+            start,
+            end,
+            outcome: outcome.to_owned(),
+        })
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Case {
+    Lower,
+    Upper,
+    Mixed,
+    Title,
+}
+
+/// Options for summarizing a list of generated numbers.
+#[derive(Debug, Clone, Copy, Args)]
+struct Summary {
+    /// Show the smallest generated number after the numbers.
+    #[clap(long)]
+    min: bool,
+    /// Show the largest generated number after the numbers.
+    #[clap(long)]
+    max: bool,
+    /// Show how many numbers fell into each of `N` equal-width buckets of the
+    /// range instead of the numbers.
+    #[clap(long, value_name = "N", conflicts_with = "raw")]
+    buckets: Option<usize>,
+}
+
+/// Options for formatting a floating point number.
+#[derive(Debug, Clone, Args)]
+struct FloatFormat {
+    /// The precision of a floating point number.
+    ///
+    /// A precision of 0 rounds the number to an integer. Precisions larger than
+    /// 17 are reduced to 17, since an `f64` isn't more precise than that.
+    #[clap(short, long, default_value_t = 6)]
+    precision: usize,
+    /// Round a floating point number to this many significant figures instead
+    /// of `precision` decimal places.
+    ///
+    /// Numbers that are too small or too large to show the figures with at most
+    /// 17 decimal places are printed in scientific notation, like `1.23e-20`.
+    #[clap(long, value_name = "N", conflicts_with = "precision")]
+    sig_figs: Option<usize>,
+    /// Use a comma as the decimal separator instead of a period.
+    ///
+    /// Several numbers are printed one per line, so the decimal comma can't be
+    /// mistaken for the separator of the list.
+    #[clap(long)]
+    decimal_comma: bool,
+    /// How a floating point number is rounded to `precision` decimal places.
+    #[clap(short, long, default_value_t = Rounding::HalfEven, value_enum)]
+    rounding: Rounding,
+}
+
+impl FloatFormat {
+    /// Formats `num` into an output value.
+    ///
+    /// With `sig_figs`, numbers whose significant figures don't fit in
+    /// [`MAX_PRECISION`] digits around the decimal point are formatted in
+    /// scientific notation instead.
+    fn format(&self, num: f64) -> Result<Value> {
+        let text = match self.sig_figs {
+            Some(0) => return Err("`sig-figs` must be at least 1".into()),
+            Some(figs) => {
+                // an `f64` doesn't have more significant figures than that
+                let figs = figs.min(MAX_PRECISION);
+                // the number of decimal places is negative when the significant
+                // figures end before the decimal point
+                let magnitude = if num == 0.0 { 0 } else { num.abs().log10().floor() as i32 };
+                let decimals = figs as i32 - 1 - magnitude;
+                if decimals > MAX_PRECISION as i32 || magnitude >= MAX_PRECISION as i32 {
+                    // the power of ten is applied in two steps, so it doesn't
+                    // overflow for numbers close to the limits of an `f64`
+                    let scale = |n: f64, e: i32| n * 10f64.powi(e / 2) * 10f64.powi(e - e / 2);
+                    let precision = figs - 1;
+                    let mantissa = self
+                        .rounding
+                        .round(scale(num, -magnitude), precision as i32);
+                    format!("{:.precision$e}", scale(mantissa, magnitude))
+                } else {
+                    let num = self.rounding.round(num, decimals);
+                    format!("{num:.*}", decimals.max(0) as usize)
+                }
+            },
+            None => {
+                let num = self.rounding.round(num, self.precision as i32);
+                format!("{num:.*}", self.precision)
+            },
+        };
+        let value = text.parse()?;
+
+        Ok(Value::Float(value, if self.decimal_comma { text.replace('.', ",") } else { text }))
+    }
+}
+
+/// A way of rounding floating point numbers.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Rounding {
+    /// Round to the nearest number, with ties going to the even digit.
+    HalfEven,
+    /// Round to the nearest number, with ties going away from zero.
+    HalfUp,
+    /// Round towards negative infinity.
+    Floor,
+    /// Round towards positive infinity.
+    Ceil,
+}
+
+impl Rounding {
+    /// Rounds `num` to `precision` decimal places.
+    ///
+    /// A negative precision rounds to a multiple of a power of ten, like `-2` to
+    /// a multiple of 100.
+    fn round(self, num: f64, precision: i32) -> f64 {
+        let scale = 10f64.powi(precision);
+        match self {
+            // formatting with a precision already rounds half to even
+            Self::HalfEven if precision >= 0 => num,
+            Self::HalfEven => (num * scale).round_ties_even() / scale,
+            Self::HalfUp => (num * scale).round() / scale,
+            Self::Floor => (num * scale).floor() / scale,
+            Self::Ceil => (num * scale).ceil() / scale,
+        }
+    }
+}
+
+/// The order in which the items chosen without repetition are shown.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Order {
+    /// The order in which the items were chosen.
+    Sampled,
+    /// The order of the items in the input.
+    Input,
+    /// The order of the labels of the items.
+    Sorted,
+    /// A random order.
+    Shuffled,
+}
+
+/// An algorithm for choosing weighted items without repetition.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Method {
+    /// Assign each item a random key based on its weight and pick the items
+    /// with the largest keys (the Efraimidis-Spirakis algorithm).
+    ///
+    /// This is fast, but relies on the weights being usable as keys, so items
+    /// with a weight of zero or less can still be chosen once no item with a
+    /// positive weight is left.
+    Efraimidis,
+    /// Pick one item at a time, normalizing the weights of the remaining items
+    /// after every pick.
+    ///
+    /// The probability of picking an item is always proportional to its weight
+    /// among the items that are left. Items with a weight of zero are never
+    /// picked, and an error is returned if not enough items have a positive weight.
+    Sequential,
+}
+
+fn random_cmd<T: Copy + PartialOrd + SampleUniform + Into<Num>>(
+    rng: &mut impl Rng,
+    lower: T,
+    upper: T,
+    inclusive: bool,
+    amount: usize,
+    summary: Summary,
+    float_format: FloatFormat,
+) -> Result<Output> {
+    let format = |num: T| match num.into() {
+        Num::Int(i) => Ok(Value::Int(i)),
+        Num::Float(f) => float_format.format(f),
+    };
+
+    let (start, end) = (lower.into().as_float(), upper.into().as_float());
+    let mut buckets = vec![0; summary.buckets.unwrap_or(0)];
+    let len = buckets.len();
+    let bucket = |num: T| {
+        let i = (num.into().as_float() - start) / (end - start) * len as f64;
+        // the upper bound of an inclusive range belongs to the last bucket
+        (i as usize).min(len - 1)
+    };
+
+    // the numbers are only kept when they aren't counted in buckets
+    let mut values = Vec::with_capacity(if buckets.is_empty() { amount } else { 0 });
+    let (mut min, mut max) = (None, None);
+
+    for i in 0..amount {
+        deadline::check_every(i)?;
+        let num =
+            if inclusive { rng.gen_range(lower..=upper) } else { rng.gen_range(lower..upper) };
+        if min.is_none_or(|min| num < min) {
+            min = Some(num);
+        }
+        if max.is_none_or(|max| num > max) {
+            max = Some(num);
+        }
+        if buckets.is_empty() {
+            values.push(format(num)?);
+        } else {
+            buckets[bucket(num)] += 1;
+        }
+    }
+
+    let output = if !buckets.is_empty() {
+        let edge = |i: usize| {
+            let edge = start + (end - start) * i as f64 / len as f64;
+            if edge.fract() == 0.0 {
+                Ok(edge.to_string())
+            } else {
+                float_format.format(edge).map(|v| v.to_string())
+            }
+        };
+        let entries = buckets
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| Ok((format!("{}..{}", edge(i)?, edge(i + 1)?), count)))
+            .collect::<Result<_>>()?;
+        Output::Counts(Counts {
+            entries,
+            total: amount as u64,
+            ..Default::default()
+        })
+    } else if amount == 1 {
+        Output::Value(values.remove(0))
+    } else if float_format.decimal_comma {
+        Output::Lines(values)
+    } else {
+        Output::List(values)
+    };
+
+    if !(summary.min || summary.max) {
+        return Ok(output);
+    }
+
+    let mut sections = vec![Section {
+        key: "values",
+        heading: None,
+        output,
+    }];
+    for (key, shown, num) in [("min", summary.min, min), ("max", summary.max, max)] {
+        if let (true, Some(num)) = (shown, num) {
+            sections.push(Section {
+                key,
+                heading: Some(key),
+                output: Output::Value(format(num)?),
+            });
+        }
+    }
+
+    Ok(Output::Sections(sections))
+}
+
+fn shuffle_cmd(
+    rng: &mut impl Rng,
+    mut items: Vec<String>,
+    k: usize,
+    chunk_size: Option<usize>,
+    trials: Option<(usize, usize)>,
+    display: SelectionDisplay,
+) -> Result<Output> {
+    if let Some(size) = chunk_size {
+        let mut chunks = items.chunks(size).collect::<Vec<_>>();
+        chunks.shuffle(rng);
+        let items = chunks.concat();
+        return Ok(Output::List(items.into_iter().map(Value::from).collect()));
+    }
+
+    let Some((trials, position)) = trials else {
+        items[..k].shuffle(rng);
+        return Ok(Output::List(items.into_iter().map(Value::from).collect()));
+    };
+
+    // shuffling the indices avoids cloning the items for every trial
+    let mut order = (0..items.len()).collect::<Vec<_>>();
+    let landed = (0..trials)
+        .map(|i| {
+            deadline::check_every(i)?;
+            order[..k].shuffle(rng);
+            Ok(order[position])
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    selections_output(landed.iter().map(|&i| &items[i]), display)
+}
+
+fn choose_with_repetition<S: Clone + Display + Ord + Hash + Into<Value>>(
+    rng: &mut impl Rng,
+    items: Vec<S>,
+    weights: Vec<f64>,
+    amount: usize,
+    display: SelectionDisplay,
+    alias_method: bool,
+) -> Result<Output> {
+    if alias_method {
+        let table = weights::AliasTable::new(&weights)?;
+        let selections = (0..amount).map(|_| &items[table.sample(rng)]);
+        return selections_output(selections, display);
+    }
+
+    let dist = WeightedIndex::new(weights)?;
+
+    let selections = (0..amount).map(|_| &items[dist.sample(rng)]);
+
+    selections_output(selections, display)
+}
+
+fn choose_without_repetition(
+    rng: &mut impl Rng,
     items: Vec<String>,
     weights: Vec<f64>,
     amount: usize,
-    count: bool,
-    all: bool,
-) -> Result<()> {
-    let choices = items
+    display: SelectionDisplay,
+    method: Method,
+    order: Order,
+) -> Result<Output> {
+    // the selections keep the index of each item for showing them in input order
+    let mut selections = match method {
+        Method::Efraimidis => {
+            let choices = items
+                .into_iter()
+                .enumerate()
+                .zip(weights)
+                .collect::<Vec<_>>();
+
+            choices
+                .choose_multiple_weighted(rng, amount, |i| i.1)?
+                .map(|(i, _)| i.clone())
+                .collect::<Vec<_>>()
+        },
+        Method::Sequential => {
+            let mut remaining = items
+                .into_iter()
+                .enumerate()
+                .zip(weights)
+                .collect::<Vec<_>>();
+            let mut selections = Vec::with_capacity(amount);
+
+            for _ in 0..amount {
+                deadline::check()?;
+                let dist = WeightedIndex::new(remaining.iter().map(|(_, w)| w))?;
+                selections.push(remaining.swap_remove(dist.sample(rng)).0);
+            }
+
+            selections
+        },
+    };
+
+    match order {
+        Order::Sampled => (),
+        Order::Input => selections.sort_unstable_by_key(|(i, _)| *i),
+        Order::Sorted => selections.sort_by(|(_, a), (_, b)| a.cmp(b)),
+        Order::Shuffled => selections.shuffle(rng),
+    }
+
+    selections_output(selections.iter().map(|(_, s)| s), display)
+}
+
+/// Returns `items` in a random order where items with larger weights are more
+/// likely to come first.
+fn weighted_order<T>(rng: &mut impl Rng, items: Vec<T>, weights: Vec<f64>) -> Result<Vec<T>> {
+    weights::check_len(&items, &weights)?;
+    if !weights.iter().all(|w| *w >= 0.0 && w.is_finite()) {
+        return Err("weights must be non-negative numbers".into());
+    }
+
+    // sorting by the keys of the Efraimidis-Spirakis algorithm gives a weighted
+    // random order, which is a uniform shuffle if all the weights are equal
+    Ok(items
+        .into_iter()
+        .zip(weights)
+        .map(|(item, w)| (rng.gen::<f64>().powf(1.0 / w), item))
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+        .map(|(_, item)| item)
+        .collect())
+}
+
+fn rank_cmd(rng: &mut impl Rng, items: Vec<String>, weights: Vec<f64>) -> Result<Output> {
+    let ranking = weighted_order(rng, items, weights)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| Value::Str(format!("{}. {item}", i + 1)))
+        .collect();
+
+    Ok(Output::List(ranking))
+}
+
+fn top_weighted_cmd(items: Vec<String>, weights: Vec<f64>, amount: usize) -> Result<Output> {
+    weights::check_len(&items, &weights)?;
+
+    // the sort is stable, so ties keep their original order
+    let top = items
         .into_iter()
-        .zip(weights.into_iter())
+        .zip(weights)
+        .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+        .take(amount)
+        .map(|(item, _)| Value::from(item))
+        .collect();
+
+    Ok(Output::List(top))
+}
+
+fn invert_cmd(
+    rng: &mut impl Rng,
+    items: Vec<String>,
+    weights: Vec<f64>,
+    amount: usize,
+) -> Result<Output> {
+    // the first `amount` indices of a weighted order are the chosen items
+    let mut kept = weighted_order(rng, (0..items.len()).collect(), weights)?.split_off(amount);
+    kept.sort_unstable();
+
+    Ok(Output::List(kept.into_iter().map(|i| items[i].as_str().into()).collect()))
+}
+
+fn selections_output<I, D>(selections: I, display: SelectionDisplay) -> Result<Output>
+where
+    I: Iterator<Item = D>,
+    D: Clone + Display + Ord + Hash + Into<Value>,
+{
+    selections_output_with_zeros(selections, iter::empty(), display)
+}
+
+/// Like [`selections_output`], but every item of `outcomes` that was never selected
+/// is counted with a count of zero.
+fn selections_output_with_zeros<I, O, D>(
+    selections: I,
+    outcomes: O,
+    display: SelectionDisplay,
+) -> Result<Output>
+where
+    I: Iterator<Item = D>,
+    O: Iterator<Item = D>,
+    D: Clone + Display + Ord + Hash + Into<Value>,
+{
+    let tally = display.count
+        || display.options.live_count
+        || display.options.entropy
+        || display.options.count_file.is_some()
+        || display.expectation.is_some();
+
+    // nothing is counted, so the selections are shown as they are, which is the
+    // common case of a single selection
+    if !tally {
+        let list = selections
+            .enumerate()
+            .map(|(i, s)| deadline::check_every(i).map(|_| s.into()))
+            .collect::<Result<_>>()?;
+        return Ok(Output::List(list));
+    }
+
+    let mut live = display
+        .options
+        .live_count
+        .then(LiveHistogram::new)
+        .flatten();
+    let mut map = HashMap::new();
+    let mut list = Vec::new();
+
+    for outcome in outcomes {
+        map.entry(outcome).or_insert(0u64);
+    }
+
+    for (i, selection) in selections.enumerate() {
+        deadline::check_every(i)?;
+        if display.all {
+            list.push(selection.clone().into());
+        }
+        *map.entry(selection).or_insert(0u64) += 1;
+        if let Some(live) = &mut live {
+            live.update(&map, false);
+        }
+    }
+
+    if let Some(live) = &mut live {
+        live.update(&map, true);
+    }
+
+    if display.options.entropy {
+        eprintln!("entropy: {:.4} bits", entropy(map.values().copied()));
+    }
+
+    if let Some(expectation) = display.expectation {
+        expectation.check(&map)?;
+    }
+
+    if display.count || display.options.count_file.is_some() {
+        let total = map.values().sum();
+        let mut entries = match &display.options.group_by {
+            Some(separator) => {
+                let mut groups = HashMap::new();
+                for (s, c) in map {
+                    let label = s.to_string();
+                    let group = match label.split_once(separator.as_str()) {
+                        Some((group, _)) => group.to_owned(),
+                        None => label,
+                    };
+                    *groups.entry(group).or_insert(0) += c;
+                }
+                display.options.sorted(groups, display.tie_seed)
+            },
+            None => display
+                .options
+                .sorted(map, display.tie_seed)
+                .into_iter()
+                .map(|(s, c)| (s.to_string(), c))
+                .collect(),
+        };
+        if let Some(path) = &display.options.count_file {
+            write_count_file(path, &entries)?;
+        }
+
+        if !display.count {
+            return Ok(Output::List(list));
+        }
+
+        let omitted = match display.options.top {
+            Some(top) if top < entries.len() => entries.split_off(top).len(),
+            _ => 0,
+        };
+        let counts = Counts {
+            entries,
+            omitted,
+            total,
+            show_total: display.options.count_total,
+            format: display.options.count_format.clone(),
+            columns: display.options.count_columns.unwrap_or(1),
+            expected: display
+                .expectation
+                .filter(|_| display.options.deviation)
+                .map(|e| e.proportions.iter().cloned().collect())
+                .unwrap_or_default(),
+            ordered: display.options.json_ordered,
+        };
+        Ok(if display.all {
+            Output::Tally {
+                selections: list,
+                counts,
+            }
+        } else {
+            Output::Counts(counts)
+        })
+    } else {
+        Ok(Output::List(list))
+    }
+}
+
+/// Writes the items and their counts to a CSV file at `path`.
+fn write_count_file(path: &Path, entries: &[(String, u64)]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)
+        .map_err(|e| format!("failed to open `{}`: {e}", path.display()))?;
+
+    writer.write_record(["item", "count"])?;
+    for (item, count) in entries {
+        writer.write_record([item, &count.to_string()])?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Returns the Shannon entropy in bits of the distribution with the given counts.
+fn entropy(counts: impl Iterator<Item = u64> + Clone) -> f64 {
+    let total = counts.clone().sum::<u64>() as f64;
+
+    counts
+        .filter(|&c| c > 0)
+        .map(|c| {
+            let p = c as f64 / total;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+fn coin_streaks_cmd(
+    rng: &mut impl Rng,
+    amount: usize,
+    display: SelectionDisplay,
+) -> Result<Output> {
+    let dist = WeightedIndex::new([1.0, 1.0])?;
+    let flips = (0..amount)
+        .map(|i| deadline::check_every(i).map(|_| COIN_SIDES[dist.sample(rng)]))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut longest = HashMap::new();
+    for (run, side) in flips.iter().dedup_with_count() {
+        let entry = longest.entry(*side).or_insert(0);
+        *entry = run.max(*entry);
+    }
+    let streaks = COIN_SIDES
+        .iter()
+        .map(|side| (side.to_string(), longest.get(side).copied().unwrap_or(0) as u64))
+        .collect();
+
+    Ok(Output::Sections(vec![
+        Section {
+            key: "flips",
+            heading: None,
+            output: selections_output(flips.iter(), display)?,
+        },
+        Section {
+            key: "streaks",
+            heading: Some("longest streaks"),
+            output: Output::Counts(Counts {
+                entries: streaks,
+                ..Default::default()
+            }),
+        },
+    ]))
+}
+
+fn coin_best_of_cmd(rng: &mut impl Rng, n: usize) -> Result<Output> {
+    let dist = WeightedIndex::new([1.0, 1.0])?;
+    let majority = n / 2 + 1;
+    let mut wins = [0; 2];
+    let mut flips = Vec::new();
+
+    // the series stops as soon as one side can't be caught up with
+    let winner = loop {
+        deadline::check_every(flips.len())?;
+        let side = dist.sample(rng);
+        flips.push(Value::from(COIN_SIDES[side]));
+        wins[side] += 1;
+        if wins[side] == majority {
+            break COIN_SIDES[side];
+        }
+    };
+
+    Ok(Output::Sections(vec![
+        Section {
+            key: "flips",
+            heading: None,
+            output: Output::List(flips),
+        },
+        Section {
+            key: "winner",
+            heading: Some("winner"),
+            output: Output::Value(winner.into()),
+        },
+    ]))
+}
+
+fn coin_trace_cmd(
+    rng: &mut impl Rng,
+    amount: usize,
+    path: &Path,
+    display: SelectionDisplay,
+) -> Result<Output> {
+    let mut writer = csv::Writer::from_path(path)
+        .map_err(|e| format!("failed to open `{}`: {e}", path.display()))?;
+    writer.write_record(["flip", "proportion"])?;
+
+    let dist = WeightedIndex::new([1.0, 1.0])?;
+    let mut heads = 0;
+    // the flips are generated lazily, so the first error is kept for later
+    let mut error = None;
+    let flips = (1..=amount).map(|flip| {
+        let side = dist.sample(rng);
+        if side == 0 {
+            heads += 1;
+        }
+        let proportion = format!("{:.4}", heads as f64 / flip as f64);
+        if error.is_none() {
+            error = writer.write_record([flip.to_string(), proportion]).err();
+        }
+        &COIN_SIDES[side]
+    });
+
+    let output = selections_output(flips, display)?;
+    if let Some(e) = error {
+        return Err(e.into());
+    }
+    writer.flush()?;
+
+    Ok(output)
+}
+
+fn coin_converge_cmd(rng: &mut impl Rng, amount: usize) -> Result<Output> {
+    let dist = WeightedIndex::new([1.0, 1.0])?;
+    let mut heads = 0;
+    let mut checkpoint = 10;
+    let mut proportions = Vec::new();
+
+    for flips in 1..=amount {
+        deadline::check_every(flips)?;
+        if dist.sample(rng) == 0 {
+            heads += 1;
+        }
+        if flips == checkpoint || flips == amount {
+            proportions.push((flips.to_string(), format!("{:.4}", heads as f64 / flips as f64)));
+            checkpoint = checkpoint.saturating_mul(10);
+        }
+    }
+
+    Ok(Output::Pairs(proportions))
+}
+
+fn string_cmd(
+    rng: &mut impl Rng,
+    characters: usize,
+    case: Case,
+    prefix: &str,
+    suffix: &str,
+    charset: Charset,
+) -> Result<Output> {
+    // alphanumeric characters and syllables are always ASCII
+    let mut s = match charset {
+        Charset::Alphanumeric => Alphanumeric.sample_string(rng, characters),
+        Charset::Unambiguous => unambiguous(rng, characters, case),
+        Charset::Syllables => syllables(rng, characters),
+    };
+
+    match case {
+        Case::Lower => s.make_ascii_lowercase(),
+        Case::Upper => s.make_ascii_uppercase(),
+        Case::Mixed => (),
+        Case::Title => {
+            s.make_ascii_lowercase();
+            if let Some(first) = s.get_mut(..1) {
+                first.make_ascii_uppercase();
+            }
+        },
+    }
+
+    Ok(Output::Value(Value::Str(format!("{prefix}{s}{suffix}"))))
+}
+
+/// Generates `length` alphanumeric characters without the [`AMBIGUOUS`] ones.
+///
+/// Each character is drawn from the characters of its case, so changing the case
+/// afterwards can't make a character ambiguous.
+fn unambiguous(rng: &mut impl Rng, length: usize, case: Case) -> String {
+    let pool = |upper: bool, lower: bool| {
+        ('0'..='9')
+            .chain(('A'..='Z').filter(|_| upper))
+            .chain(('a'..='z').filter(|_| lower))
+            .filter(|c| !AMBIGUOUS.contains(*c))
+            .collect::<Vec<_>>()
+    };
+    let (first, rest) = match case {
+        Case::Lower => (pool(false, true), pool(false, true)),
+        Case::Upper => (pool(true, false), pool(true, false)),
+        Case::Mixed => (pool(true, true), pool(true, true)),
+        Case::Title => (pool(true, false), pool(false, true)),
+    };
+
+    (0..length)
+        .map(|i| *if i == 0 { &first } else { &rest }.choose(rng).unwrap())
+        .collect()
+}
+
+/// Generates consonant-vowel syllables, some ending in another consonant, until
+/// the string is at least `length` characters long.
+fn syllables(rng: &mut impl Rng, length: usize) -> String {
+    let mut s = String::with_capacity(length + 2);
+
+    while s.len() < length {
+        s.push(*CONSONANTS.choose(rng).unwrap() as char);
+        s.push(*VOWELS.choose(rng).unwrap() as char);
+        if rng.gen_ratio(1, 4) {
+            s.push(*CONSONANTS.choose(rng).unwrap() as char);
+        }
+    }
+
+    s
+}
+
+/// The characters a string is made of.
+#[derive(Debug, Clone, Copy)]
+enum Charset {
+    /// Letters and digits.
+    Alphanumeric,
+    /// Letters and digits without the [`AMBIGUOUS`] ones.
+    Unambiguous,
+    /// Syllables that can be read aloud.
+    Syllables,
+}
+
+/// Which rolls of a die are kept.
+#[derive(Debug, Clone, Copy)]
+enum Keep {
+    /// Keep every roll.
+    All,
+    /// Keep the given number of highest rolls.
+    Highest(usize),
+    /// Keep the given number of lowest rolls.
+    Lowest(usize),
+}
+
+/// Options for reporting the rolls of a die.
+#[derive(Debug, Clone)]
+struct DieReport {
+    /// The number of rolls between two running counts, if they are printed.
+    count_interval: Option<usize>,
+    /// The number added to the total of the rolls, if the total is shown.
+    modifier: Option<i128>,
+    /// The value of each of the faces, if they are given and the total is shown.
+    face_values: Vec<i128>,
+    /// Whether the faces that were never rolled are counted.
+    show_zero: bool,
+}
+
+fn die_cmd(
+    rng: &mut impl Rng,
+    sides: usize,
+    faces: &[String],
+    times: usize,
+    keep: Keep,
+    report: DieReport,
+    display: SelectionDisplay,
+) -> Result<Output> {
+    let DieReport {
+        count_interval,
+        modifier,
+        face_values: values,
+        show_zero,
+    } = report;
+
+    if !faces.is_empty() {
+        // the faces are summed as they are rolled, so the rolls don't have to be
+        // kept when only the count is shown
+        let distr = Uniform::new(0, faces.len());
+        let mut snapshots = count_interval.map(CountSnapshots::new);
+        let mut total = 0;
+        let selections = distr
+            .sample_iter(rng)
+            .take(times)
+            .inspect(|&i| {
+                if let Some(value) = values.get(i) {
+                    total += value;
+                }
+            })
+            .map(|i| &faces[i])
+            .inspect(|s| {
+                if let Some(snapshots) = &mut snapshots {
+                    snapshots.record(s);
+                }
+            });
+
+        let outcomes = faces.iter().filter(|_| show_zero);
+        let output = selections_output_with_zeros(selections, outcomes, display)?;
+
+        return Ok(match modifier {
+            Some(modifier) => with_total(output, total + modifier),
+            None => output,
+        });
+    }
+
+    let distr = Uniform::new_inclusive(1, sides);
+    let mut snapshots = count_interval.map(CountSnapshots::new);
+    let rolls = distr.sample_iter(rng).take(times).inspect(|s| {
+        if let Some(snapshots) = &mut snapshots {
+            snapshots.record(s);
+        }
+    });
+    let outcomes = (1..=sides).filter(|_| show_zero);
+
+    // the rolls are only kept when some of them are dropped, otherwise they are
+    // counted and summed as they are rolled
+    let mut total = 0;
+    let output = match keep {
+        Keep::All => {
+            let rolls = rolls.inspect(|&s| total += s as i128);
+            selections_output_with_zeros(rolls, outcomes, display)?
+        },
+        Keep::Highest(k) | Keep::Lowest(k) => {
+            let mut selections = rolls
+                .enumerate()
+                .map(|(i, s)| deadline::check_every(i).map(|_| s))
+                .collect::<Result<Vec<_>>>()?;
+            if let Keep::Highest(_) = keep {
+                selections.sort_unstable_by(|a, b| b.cmp(a));
+            } else {
+                selections.sort_unstable();
+            }
+            selections.truncate(k);
+            total = selections.iter().map(|&s| s as i128).sum();
+            selections_output_with_zeros(selections.into_iter(), outcomes, display)?
+        },
+    };
+
+    Ok(match modifier {
+        Some(modifier) => with_total(output, total + modifier),
+        None => output,
+    })
+}
+
+/// Adds the `total` of the rolls after the `output` of a die.
+fn with_total(output: Output, total: i128) -> Output {
+    Output::Sections(vec![
+        Section {
+            key: "rolls",
+            heading: None,
+            output,
+        },
+        Section {
+            key: "total",
+            heading: Some("total"),
+            output: Output::Value(total.into()),
+        },
+    ])
+}
+
+fn assign_cmd(
+    rng: &mut impl Rng,
+    left: Vec<String>,
+    mut right: Vec<String>,
+    weights: Option<Vec<f64>>,
+    trials: Option<usize>,
+    display: SelectionDisplay,
+) -> Result<Output> {
+    let Some(trials) = trials else {
+        match weights {
+            Some(weights) => right = weighted_order(rng, right, weights)?,
+            None => right.shuffle(rng),
+        }
+        return Ok(Output::Pairs(left.into_iter().zip(right).collect()));
+    };
+
+    // every possible pair is labeled once, and the trials arrange the indices
+    let labels = left
+        .iter()
+        .flat_map(|l| right.iter().map(move |r| format!("{l}:{r}")))
         .collect::<Vec<_>>();
+    let n = right.len();
+    let mut arrangements = Vec::with_capacity(trials * n);
+    for _ in 0..trials {
+        deadline::check()?;
+        let order = match &weights {
+            Some(weights) => weighted_order(rng, (0..n).collect(), weights.clone())?,
+            None => {
+                let mut order = (0..n).collect::<Vec<_>>();
+                order.shuffle(rng);
+                order
+            },
+        };
+        arrangements.extend(order.into_iter().enumerate().map(|(l, r)| l * n + r));
+    }
+
+    selections_output(arrangements.iter().map(|&i| &labels[i]), display)
+}
+
+fn merge_cmd(rng: &mut impl Rng, left: Vec<String>, right: Vec<String>) -> Output {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+
+    // drawing from each list with a probability proportional to the number of
+    // items left in it makes every interleaving equally likely
+    while let (Some(_), Some(_)) = (left.peek(), right.peek()) {
+        let (l, r) = (left.len(), right.len());
+        let next = if rng.gen_range(0..l + r) < l { left.next() } else { right.next() };
+        merged.extend(next.map(Value::from));
+    }
+    merged.extend(left.chain(right).map(Value::from));
+
+    Output::List(merged)
+}
+
+fn reservoir_cmd(rng: &mut impl Rng, amount: usize) -> Result<Output> {
+    let mut reservoir = Vec::with_capacity(amount);
+
+    for (i, line) in stdin().lines().enumerate() {
+        deadline::check()?;
+        let line = line?;
+        if i < amount {
+            reservoir.push(line);
+        } else {
+            let j = rng.gen_range(0..=i);
+            if j < amount {
+                reservoir[j] = line;
+            }
+        }
+    }
+
+    Ok(Output::Lines(reservoir.into_iter().map(Value::from).collect()))
+}
+
+/// Rolls on a table of `entries`, which must be sorted, contiguous and not empty.
+fn table_cmd(rng: &mut impl Rng, entries: Vec<TableEntry>) -> Output {
+    let (first, last) = (&entries[0], &entries[entries.len() - 1]);
+
+    let roll = rng.gen_range(first.start..=last.end);
+    let entry = entries
+        .iter()
+        .find(|e| e.start <= roll && roll <= e.end)
+        .expect("ranges are contiguous");
+
+    Output::Value(Value::Str(entry.outcome.clone()))
+}
+
+/// Returns the output for a zero `amount`.
+///
+/// Nothing is printed if `allow_empty` is true, otherwise an error is returned.
+fn empty_output(amount: &str, allow_empty: bool) -> Result<Output> {
+    if allow_empty {
+        Ok(Output::Empty)
+    } else {
+        Err(format!("`{amount}` must be at least 1").into())
+    }
+}
 
-    let selections = choices
-        .choose_multiple_weighted(&mut rand::thread_rng(), amount, |i| i.1)?
-        .map(|(i, _)| i);
-    print_selections(selections, count, all, amount);
+fn examples_cmd(color: ColorChoice) -> Result<()> {
+    let bufwtr = BufferWriter::stdout(color);
+    let mut buffer = bufwtr.buffer();
+
+    for (i, (example, description)) in EXAMPLES.iter().enumerate() {
+        if i > 0 {
+            writeln!(&mut buffer)?;
+        }
+        buffer.set_color(ColorSpec::new().set_dimmed(true))?;
+        writeln!(&mut buffer, "# {description}")?;
+        buffer.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
+        write!(&mut buffer, "$ ")?;
+        buffer.reset()?;
+        writeln!(&mut buffer, "{example}")?;
+    }
+
+    bufwtr.print(&buffer)?;
 
     Ok(())
 }
 
-fn print_selections<'a, I, D>(mut selections: I, count: bool, all: bool, amount: usize)
-where
-    I: Iterator<Item = &'a D>,
-    D: 'a + Display + Eq + Hash,
-{
-    if count {
-        let mut map = HashMap::new();
-        for (i, selection) in selections.enumerate() {
-            let entry = map.entry(selection).or_insert(0u64);
-            *entry += 1;
-            if all {
-                print!("{}", selection);
-                if i != amount - 1 {
-                    print!(", ");
+/// Returns the length of the range that `amount` integers at least `gap` apart
+/// are drawn from without replacement.
+///
+/// Drawing from a range shrunk by the sum of the gaps and then spreading the
+/// sorted integers apart keeps every valid draw equally likely.
+fn draw_reduced_len(start: i64, end: i64, amount: usize, gap: usize) -> Result<usize> {
+    let len = usize::try_from(end.abs_diff(start))
+        .ok()
+        .and_then(|len| len.checked_add(1))
+        .ok_or("range is too large to draw from without replacement")?;
+    let reduced = amount
+        .saturating_sub(1)
+        .checked_mul(gap - 1)
+        .and_then(|spread| len.checked_sub(spread))
+        .filter(|&reduced| amount <= reduced);
+
+    reduced.ok_or_else(|| {
+        if gap == 1 {
+            format!("cannot draw {amount} integers from a range of {len}")
+        } else {
+            format!("cannot draw {amount} integers at least {gap} apart from a range of {len}")
+        }
+        .into()
+    })
+}
+
+/// Draws `amount` integers from `start..=end`.
+///
+/// The integers are drawn with replacement if `reduced` is `None`, otherwise
+/// they are drawn without replacement from a range of that length (see
+/// [`draw_reduced_len`]).
+fn draw_cmd(
+    rng: &mut impl Rng,
+    start: i64,
+    end: i64,
+    amount: usize,
+    gap: usize,
+    reduced: Option<usize>,
+) -> Result<Output> {
+    let values = match reduced {
+        None => Uniform::new_inclusive(start, end)
+            .sample_iter(rng)
+            .take(amount)
+            .enumerate()
+            .map(|(i, v)| deadline::check_every(i).map(|_| v))
+            .collect::<Result<Vec<_>>>()?,
+        Some(reduced) => {
+            // the indices are sampled in a single call, which can't be aborted
+            let mut indices = index::sample(rng, reduced, amount).into_vec();
+            if gap > 1 {
+                indices.sort_unstable();
+                for (i, index) in indices.iter_mut().enumerate() {
+                    *index += i * (gap - 1);
                 }
             }
+            indices
+                .into_iter()
+                .map(|i| start.wrapping_add(i as i64))
+                .collect()
+        },
+    };
+
+    Ok(Output::List(values.into_iter().map(|v| Value::Int(v.into())).collect()))
+}
+
+fn benchmark_cmd(args: Vec<String>, iterations: u32, dry_run: bool) -> Result<()> {
+    let app = Cli::try_parse_from(iter::once(crate_name!().to_owned()).chain(args))
+        .unwrap_or_else(|e| e.exit());
+    let format = app.format();
+    let mut rng = app.rng();
+    let options = app.run_options();
+    let command = app.command.unwrap_or_default();
+    command.check_amount(app.max_amount)?;
+    if dry_run {
+        return Ok(());
+    }
+
+    let start = Instant::now();
+
+    for _ in 0..iterations {
+        run_command(&mut rng, command.clone(), options)?.write(&mut sink(), format)?;
+    }
+
+    let elapsed = start.elapsed();
+    eprintln!(
+        "{iterations} samples in {elapsed:.3?} ({:.0} samples/sec)",
+        f64::from(iterations) / elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+fn eval_cmd(app: &Cli, format: Format) -> Result<()> {
+    let mut rng = app.rng();
+    // a dry run only checks the expressions
+    let mut writer = if app.dry_run { Box::new(sink()) } else { app.writer()? };
+
+    for (i, line) in stdin().lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
         }
-        if all {
-            println!("\n");
+        match line.parse::<dice::Expr>() {
+            Ok(_) if app.dry_run => (),
+            Ok(expr) => Output::Value(expr.roll(&mut rng).into()).write(&mut writer, format)?,
+            Err(e) => warn(format_args!("line {}: {e}", i + 1)),
         }
-        println!(
-            "{}",
-            map.into_iter()
-                .sorted_by(|(_, a), (_, b)| b.cmp(a))
-                .map(|(s, c)| format!("{s}: {c}"))
-                .join("\n")
-        );
-    } else {
-        println!("{}", selections.join(", "));
+        // every result is written right away for programs waiting on it
+        writer.flush()?;
     }
+
+    Ok(())
 }
 
-fn string_cmd(characters: usize, case: Case) {
-    let mut s = Alphanumeric.sample_string(&mut rand::thread_rng(), characters);
+fn replay_cmd(file: &Path, dry_run: bool) -> Result<()> {
+    let params = fs::read_to_string(file)
+        .map_err(|e| format!("failed to read `{}`: {e}", file.display()))?;
+    let params = serde_json::from_str::<Params>(&params)
+        .map_err(|e| format!("invalid parameters in `{}`: {e}", file.display()))?;
 
-    match case {
-        Case::Lower => s.make_ascii_lowercase(),
-        Case::Upper => s.make_ascii_uppercase(),
-        Case::Mixed => (),
-    }
+    let mut app = Cli::try_parse_from(iter::once(crate_name!().to_owned()).chain(params.args))
+        .unwrap_or_else(|e| e.exit());
+    app.seed = Some(params.seed);
+    app.seed_file = None;
+    // the output file of the recorded run is where the parameters were written,
+    // so the replay writes to the `stdout` instead
+    app.output_file = None;
+    app.append = false;
+    app.force = false;
+    // the saved seed already has the entropy mixed in
+    app.seed_entropy = None;
+    app.dry_run |= dry_run;
 
-    println!("{s}");
+    run_cli(app)
 }
 
-fn die_cmd(sides: usize, times: usize, count: bool, all: bool) -> Result<()> {
-    if sides < 1 {
-        return Err("number of sides must be at least 1".into());
+/// Returns the seed stored in the file at `path`.
+///
+/// If the file doesn't exist, a random seed is saved to it and returned. If
+/// `advance` is set, the seed in the file is incremented.
+fn load_seed(path: &Path, advance: bool) -> Result<u64> {
+    let seed = if path.exists() {
+        let seed = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read `{}`: {e}", path.display()))?;
+        parse_seed(seed.trim()).map_err(|e| format!("invalid seed in `{}`: {e}", path.display()))?
+    } else {
+        rand::random()
+    };
+
+    if advance || !path.exists() {
+        let next = if advance { seed.wrapping_add(1) } else { seed };
+        fs::write(path, format!("{next}\n"))
+            .map_err(|e| format!("failed to write `{}`: {e}", path.display()))?;
     }
 
-    let mut rng = rand::thread_rng();
+    Ok(seed)
+}
 
-    let distr = Uniform::new_inclusive(1, sides);
-    let roll_die = distr.sample_iter(&mut rng);
+fn run_cli(mut app: Cli) -> Result<()> {
+    let format = app.format();
 
-    let selections = roll_die.take(times).collect::<Vec<_>>();
-    print_selections(selections.iter(), count, all, times);
+    // a dry run doesn't create or advance the seed file
+    if let (Some(path), false) = (&app.seed_file, app.dry_run) {
+        app.seed = Some(load_seed(path, app.advance_seed)?);
+    }
 
-    Ok(())
-}
+    if let (Some(seed), Some(entropy)) = (app.seed, app.seed_entropy) {
+        if !matches!(entropy, SeedEntropy::None) {
+            app.seed = Some(seed ^ mix(entropy.value()?));
+        }
+    }
 
-fn assign_cmd(left: &[impl Display], right: &mut [impl Display]) -> Result<()> {
-    if left.len() != right.len() {
-        return Err("`left` and `right` lists of unequal length".into());
+    if app.version_json {
+        let version = serde_json::json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        println!("{version}");
+        return Ok(());
     }
 
-    let mut rng = rand::thread_rng();
-    right.shuffle(&mut rng);
+    if app.emit_params {
+        let params = Params {
+            seed: app.seed.unwrap_or_else(rand::random),
+            args: env::args_os()
+                .skip(1)
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .filter(|arg| arg != "--emit-params")
+                .collect(),
+        };
+        if app.dry_run {
+            return Ok(());
+        }
+        let mut writer = app.writer()?;
+        serde_json::to_writer_pretty(&mut writer, &params)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        return Ok(());
+    }
 
-    println!(
-        "{}",
-        left.iter()
-            .zip(right.iter())
-            .map(|(l, r)| format!("{l}: {r}"))
-            .join("\n")
-    );
+    match app.command.take().unwrap_or_default() {
+        Command::Benchmark {
+            iterations,
+            command,
+        } => benchmark_cmd(command, iterations, app.dry_run),
+        Command::Examples if app.dry_run => Ok(()),
+        Command::Examples => examples_cmd(color_choice(app.no_color, stdout().is_terminal())),
+        Command::Replay {
+            file,
+        } => replay_cmd(&file, app.dry_run),
+        Command::Eval => eval_cmd(&app, format),
+        command => {
+            command.check_amount(app.max_amount)?;
+            let start = Instant::now();
+            if (app.verbose || app.print_seed) && !app.secure {
+                app.seed = app.seed.or_else(|| Some(rand::random()));
+            }
+            if app.verbose {
+                eprintln!("{}", app.rng_description());
+            }
+            if let (true, Some(seed)) = (app.print_seed, app.seed) {
+                eprintln!("seed: {seed}");
+            }
 
-    Ok(())
+            let output = run_command(&mut app.rng(), command, app.run_options())?;
+            if app.dry_run {
+                return Ok(());
+            }
+            let mut writer = app.writer()?;
+            if let Some(line) = &app.prefix_line {
+                writeln!(writer, "{line}")?;
+            }
+            output.write(&mut writer, format)?;
+            if let Some(line) = &app.suffix_line {
+                writeln!(writer, "{line}")?;
+            }
+            writer.flush()?;
+
+            if app.verbose {
+                eprintln!("finished in {:.3?}", start.elapsed());
+            }
+            Ok(())
+        },
+    }
 }
 
-fn run_cli() -> Result<()> {
-    let app = Cli::parse();
+/// Runs `command` and returns its output.
+///
+/// Every command checks its arguments before generating anything, and returns
+/// [`Output::Empty`] instead on a dry run.
+fn run_command(rng: &mut impl Rng, command: Command, options: RunOptions) -> Result<Output> {
+    let RunOptions {
+        ascii_only,
+        max_amount,
+        dry_run,
+        tie_seed,
+    } = options;
 
-    match app.command.unwrap_or_default() {
+    let output = match command {
         Command::Coin {
             amount,
+            allow_empty,
             count,
             all,
-            ..
+            count_options,
+            streaks,
+            converge,
+            best_of,
+            trace_file,
         } => {
-            let all = all || amount <= AMOUNT_THRESHOLD;
-            let count = count || !all;
+            if best_of.is_some_and(|n| n.is_multiple_of(2)) {
+                return Err("`best-of` must be an odd number".into());
+            }
+
+            if best_of.is_none() && amount == 0 {
+                return empty_output("amount", allow_empty);
+            }
+
+            let expectation = count_options
+                .deviation
+                .then(|| Expectation::new(&COIN_SIDES.map(String::from), &[1.0, 1.0], None))
+                .flatten();
+            let mut display = SelectionDisplay::new(tie_seed, count, all, amount, &count_options);
+            display.expectation = expectation.as_ref();
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
 
-            choose_with_repetition(vec!["heads", "tails"], vec![1.0, 1.0], amount, count, all)?
+            if let Some(n) = best_of {
+                coin_best_of_cmd(rng, n)?
+            } else if converge {
+                coin_converge_cmd(rng, amount)?
+            } else if streaks {
+                coin_streaks_cmd(rng, amount, display)?
+            } else if let Some(path) = trace_file {
+                coin_trace_cmd(rng, amount, &path, display)?
+            } else {
+                choose_with_repetition(
+                    rng,
+                    COIN_SIDES.to_vec(),
+                    vec![1.0, 1.0],
+                    amount,
+                    display,
+                    false,
+                )?
+            }
         },
         Command::Choose {
             amount,
-            mut weights,
-            items,
+            allow_empty,
+            weights,
+            weights_delimiter,
+            pad_weights,
+            uniform,
+            interactive_weights,
+            cumulative_weights,
+            log_weights,
+            weight_by_frequency,
+            empirical,
+            softmax,
+            temperature,
+            min_weight,
+            mut items,
+            range,
             count,
             all,
+            count_options,
             repetition,
-            ..
+            method,
+            alias_method,
+            order,
+            show_indices,
+            show_weights,
+            rank,
+            assert_within,
+            invert,
+            top_weighted,
         } => {
-            if weights.is_empty() {
+            if amount == Amount::Count(0) {
+                return empty_output("amount", allow_empty);
+            }
+
+            if let Some(range) = range {
+                match range.len() {
+                    Some(len) if len <= max_amount as u64 => {},
+                    len => {
+                        let len = len.map_or_else(|| "too many".to_owned(), |l| l.to_string());
+                        return Err(format!(
+                            "the range has {len} items, more than the maximum of {max_amount}, \
+                             use `--max-amount` to allow it"
+                        )
+                        .into());
+                    },
+                }
+                items = range.items();
+            }
+
+            if items.is_empty() {
+                return Err("no items to choose from".into());
+            }
+
+            let mut weights = match weights {
+                Some(weights) => weights::parse(&weights, weights_delimiter)?,
+                None => Vec::new(),
+            };
+            if interactive_weights {
+                weights = prompt_weights(&items)?;
+            }
+            weights::check_finite(&weights)?;
+
+            if cumulative_weights {
+                weights = weights::from_cumulative(&weights)?;
+            }
+
+            if log_weights {
+                weights = weights::from_log10(&weights)?;
+            }
+
+            if weight_by_frequency {
+                (items, weights) = weights::by_frequency(items);
+            }
+
+            if let Some(path) = empirical {
+                (items, weights) = weights::from_csv(&path)?;
+            }
+
+            if uniform || weights.is_empty() {
                 weights = [1.0].repeat(items.len())
             }
 
-            let all = all || amount <= AMOUNT_THRESHOLD;
-            let count = count || !all;
+            if pad_weights && weights.len() > items.len() {
+                warn(format_args!(
+                    "ignoring {} weights without an item",
+                    weights.len() - items.len()
+                ));
+            }
+            if pad_weights {
+                weights.resize(items.len(), 1.0);
+            }
+            weights::check_len(&items, &weights)?;
+
+            if softmax {
+                weights = weights::softmax(&weights, temperature)?;
+            }
+
+            if let Some(floor) = min_weight {
+                if !(floor >= 0.0 && floor.is_finite()) {
+                    return Err("`min-weight` must be a non-negative number".into());
+                }
+                for w in &mut weights {
+                    *w = w.max(floor);
+                }
+            }
+
+            if !top_weighted && weights.iter().any(|w| *w < 0.0) {
+                return Err("weights must be non-negative numbers".into());
+            }
+
+            if show_weights {
+                if let Some(probabilities) = weights::normalize(&weights) {
+                    // the percentages are apportioned in hundredths, so they sum to 100
+                    let probabilities = items
+                        .iter()
+                        .zip(weights::apportion(&probabilities, 10000))
+                        .map(|(s, p)| format!("{s}: {}%", p as f64 / 100.0))
+                        .join(", ");
+                    eprintln!("{probabilities}");
+                }
+            }
+
+            if show_indices {
+                items = items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, s)| format!("{s} ({i})"))
+                    .collect();
+            }
+
+            // a percentage only goes past all the items when choosing with repetition
+            let amount = match amount {
+                Amount::Percent(_) if !repetition => amount.of(items.len()).min(items.len()),
+                _ => amount.of(items.len()),
+            };
+
+            if rank || top_weighted || invert {
+                if invert && amount > items.len() {
+                    return Err("cannot leave out more items than there are".into());
+                }
+
+                if dry_run {
+                    return Ok(Output::Empty);
+                }
+
+                return if rank {
+                    rank_cmd(rng, items, weights)
+                } else if top_weighted {
+                    top_weighted_cmd(items, weights, amount)
+                } else {
+                    invert_cmd(rng, items, weights, amount)
+                };
+            }
+
+            let expectation = (assert_within.is_some() || count_options.deviation)
+                .then(|| Expectation::new(&items, &weights, assert_within))
+                .flatten();
+            if assert_within.is_some() && expectation.is_none() {
+                return Err("`assert-within` requires weights with a positive sum".into());
+            }
+
+            check_max_amount(amount, max_amount)?;
+            if amount > items.len() && !matches!(order, Order::Sampled) {
+                return Err("`order` can't be used when `amount` is larger than the number of \
+                            items, which chooses with repetition"
+                    .into());
+            }
+            let repetition = repetition || amount > items.len();
+            if assert_within.is_some() && !repetition {
+                return Err("`assert-within` requires choosing with repetition".into());
+            }
+            let positive = weights.iter().filter(|w| **w > 0.0).count();
+            if repetition && positive == 0 {
+                return Err("at least one weight must be positive".into());
+            }
+            if !repetition && matches!(method, Method::Sequential) && positive < amount {
+                return Err(format!(
+                    "cannot choose {amount} items without repetition when only {positive} have a \
+                     positive weight"
+                )
+                .into());
+            }
+            let mut display = SelectionDisplay::new(tie_seed, count, all, amount, &count_options);
+            display.expectation = expectation.as_ref();
 
-            if repetition || amount > items.len() {
-                choose_with_repetition(items, weights, amount, count, all)?;
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            if repetition {
+                choose_with_repetition(rng, items, weights, amount, display, alias_method)?
             } else {
-                choose_without_repetition(items, weights, amount, count, all)?;
+                choose_without_repetition(rng, items, weights, amount, display, method, order)?
             }
         },
         Command::Shuffle {
-            mut items, ..
-        } => shuffle_cmd(&mut items),
+            items,
+            partial,
+            chunk_size,
+            trials,
+            position,
+            count_options,
+        } => {
+            // only the first `k` items are shuffled
+            let k = partial.map_or(items.len(), |k| k.of(items.len()));
+            let trials = trials.zip(position);
+            if chunk_size == Some(0) {
+                return Err("`chunk-size` must be at least 1".into());
+            }
+            // chunks are shuffled whole, so the other options don't apply to them
+            if chunk_size.is_none() {
+                if k > items.len() {
+                    return Err("`partial` must not be greater than the number of items".into());
+                }
+                if let Some((trials, position)) = trials {
+                    if trials == 0 {
+                        return Err("`trials` must be at least 1".into());
+                    }
+                    if position >= items.len() {
+                        return Err("`position` must be less than the number of items".into());
+                    }
+                }
+            }
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            let display = SelectionDisplay::count_only(tie_seed, &count_options);
+            shuffle_cmd(rng, items, k, chunk_size, trials, display)?
+        },
         Command::Random {
-            mut start,
-            mut end,
+            start,
+            end,
             inclusive,
-            precision,
-            ..
+            amount,
+            summary,
+            mut float_format,
+            raw,
         } => {
-            if let (Some(s), true) = (start, end.is_none()) {
-                if s.as_float() < 0.0 {
-                    end = Some(Num::INT_0);
-                } else {
-                    end = start;
-                    start = Some(Num::INT_0);
-                }
+            if amount == 0 {
+                return empty_output("amount", false);
+            }
+
+            let (start, end) = infer_range(start, end);
+            let empty = match (start, end) {
+                (Num::Int(s), Num::Int(e)) => s >= e,
+                (s, e) => s.as_float() >= e.as_float(),
+            };
+            if empty {
+                return Err("lower bound should be smaller than upper".into());
+            }
+
+            if float_format.precision > MAX_PRECISION {
+                warn(format_args!(
+                    "precision {} is larger than the maximum of {MAX_PRECISION}, using \
+                     {MAX_PRECISION}",
+                    float_format.precision
+                ));
+                float_format.precision = MAX_PRECISION;
             }
 
-            match (start.unwrap_or(Num::FLOAT_0), end.unwrap_or(Num::FLOAT_1)) {
-                (Num::Int(s), Num::Int(e)) => random_cmd(s, e, inclusive, precision),
-                (Num::Int(s), Num::Float(e)) => random_cmd(s as f64, e, inclusive, precision),
-                (Num::Float(s), Num::Int(e)) => random_cmd(s, e as f64, inclusive, precision),
-                (Num::Float(s), Num::Float(e)) => random_cmd(s, e, inclusive, precision),
-            }?
+            if summary.buckets == Some(0) {
+                return Err("`buckets` must be at least 1".into());
+            }
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            let output = match (start, end) {
+                (Num::Int(s), Num::Int(e)) => {
+                    random_cmd(rng, s, e, inclusive, amount, summary, float_format)
+                },
+                (Num::Int(s), Num::Float(e)) => {
+                    random_cmd(rng, s as f64, e, inclusive, amount, summary, float_format)
+                },
+                (Num::Float(s), Num::Int(e)) => {
+                    random_cmd(rng, s, e as f64, inclusive, amount, summary, float_format)
+                },
+                (Num::Float(s), Num::Float(e)) => {
+                    random_cmd(rng, s, e, inclusive, amount, summary, float_format)
+                },
+            }?;
+            if raw {
+                output.into_raw()
+            } else {
+                output
+            }
         },
         Command::String {
             length: characters,
             case,
-            ..
-        } => string_cmd(characters, case),
+            prefix,
+            suffix,
+            pronounceable,
+            no_ambiguous,
+        } => {
+            let charset = if pronounceable {
+                Charset::Syllables
+            } else if no_ambiguous {
+                Charset::Unambiguous
+            } else {
+                Charset::Alphanumeric
+            };
+
+            if ascii_only && !(prefix.is_ascii() && suffix.is_ascii()) {
+                return Err("`prefix` and `suffix` must be ASCII with `ascii-only`".into());
+            }
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            string_cmd(rng, characters, case, &prefix, &suffix, charset)?
+        },
         Command::Die {
             sides,
             times,
+            allow_empty,
+            faces,
             count,
             all,
-            ..
+            count_interval,
+            total,
+            modifier,
+            show_zero,
+            keep_highest,
+            keep_lowest,
+            count_options,
+            raw,
         } => {
-            let all = all || times <= AMOUNT_THRESHOLD;
-            let count = count || !all;
+            if times == 0 {
+                return empty_output("times", allow_empty);
+            }
+
+            if count_interval == Some(0) {
+                return Err("`count-interval` must be at least 1".into());
+            }
+
+            let keep = match (keep_highest, keep_lowest) {
+                (Some(k), _) => Keep::Highest(k),
+                (_, Some(k)) => Keep::Lowest(k),
+                (None, None) => Keep::All,
+            };
+            // the faces are rolled as they are, so only numeric rolls are kept
+            if faces.is_empty() {
+                if sides < 1 {
+                    return Err("number of sides must be at least 1".into());
+                }
+                if let Keep::Highest(k) | Keep::Lowest(k) = keep {
+                    if k > times {
+                        return Err("cannot keep more rolls than `times`".into());
+                    }
+                }
+            }
+
+            // the faces are parsed up front so they can be summed as they are rolled
+            let face_values = if total {
+                faces
+                    .iter()
+                    .map(|s| s.parse::<i128>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| "`total` requires every face to be an integer")?
+            } else {
+                Vec::new()
+            };
+            let kept = keep_highest.or(keep_lowest).unwrap_or(times).min(times);
+            // keeping some of the rolls changes their distribution, so only every
+            // roll has a known expectation
+            let expectation = (count_options.deviation && matches!(keep, Keep::All))
+                .then(|| {
+                    let outcomes = if faces.is_empty() {
+                        (1..=sides).map(|i| i.to_string()).collect()
+                    } else {
+                        faces.clone()
+                    };
+                    Expectation::new(&outcomes, &vec![1.0; outcomes.len()], None)
+                })
+                .flatten();
+            let mut display =
+                SelectionDisplay::new(tie_seed, count, all || raw, kept, &count_options);
+            display.expectation = expectation.as_ref();
+
+            let report = DieReport {
+                count_interval,
+                modifier: total.then_some(modifier),
+                face_values,
+                show_zero,
+            };
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
 
-            die_cmd(sides, times, count, all)?
+            let output = die_cmd(rng, sides, &faces, times, keep, report, display)?;
+            if raw {
+                output.into_raw()
+            } else {
+                output
+            }
         },
         Command::Assign {
             left,
-            mut right,
-        } => assign_cmd(&left, &mut right)?,
+            right,
+            strict,
+            weights,
+            trials,
+            count: _,
+            count_options,
+        } => {
+            if left.len() != right.len() {
+                return Err("`left` and `right` lists of unequal length".into());
+            }
+
+            if strict {
+                if let Some(item) = left.iter().duplicates().next() {
+                    return Err(format!("`{item}` appears more than once in `left`").into());
+                }
+            }
+
+            let weights = weights
+                .map(|weights| -> Result<Vec<f64>> {
+                    let weights = weights::parse(&weights, ',')?;
+                    weights::check_finite(&weights)?;
+                    weights::check_len(&right, &weights)?;
+                    if weights.iter().any(|w| *w < 0.0) {
+                        return Err("weights must be non-negative numbers".into());
+                    }
+                    Ok(weights)
+                })
+                .transpose()?;
+
+            if trials == Some(0) {
+                return Err("`trials` must be at least 1".into());
+            }
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            let display = SelectionDisplay::count_only(tie_seed, &count_options);
+            assign_cmd(rng, left, right, weights, trials, display)?
+        },
+        Command::Merge {
+            left,
+            right,
+        } => {
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            merge_cmd(rng, left, right)
+        },
+        Command::Reservoir {
+            amount,
+        } => {
+            // the stream is only read when sampling
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            reservoir_cmd(rng, amount)?
+        },
+        Command::Table {
+            mut entries,
+        } => {
+            entries.sort_by_key(|e| e.start);
+
+            for (prev, next) in entries.iter().tuple_windows() {
+                if next.start <= prev.end {
+                    return Err(format!(
+                        "ranges `{}-{}` and `{}-{}` overlap",
+                        prev.start, prev.end, next.start, next.end
+                    )
+                    .into());
+                }
+                if next.start != prev.end + 1 {
+                    return Err(format!(
+                        "no outcome for rolls between {} and {}",
+                        prev.end, next.start
+                    )
+                    .into());
+                }
+            }
+
+            if entries.is_empty() {
+                return Err("the table must have at least one entry".into());
+            }
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            table_cmd(rng, entries)
+        },
+        Command::Benchmark {
+            ..
+        } => return Err("the benchmark command cannot be benchmarked".into()),
+        Command::Draw {
+            start,
+            end,
+            amount,
+            replacement,
+            min_gap,
+        } => {
+            if start > end {
+                return Err("lower bound should not be larger than upper".into());
+            }
+
+            let gap = min_gap.unwrap_or(1);
+            if gap == 0 {
+                return Err("`min-gap` must be at least 1".into());
+            }
+
+            let reduced =
+                if replacement { None } else { Some(draw_reduced_len(start, end, amount, gap)?) };
+
+            if dry_run {
+                return Ok(Output::Empty);
+            }
+
+            draw_cmd(rng, start, end, amount, gap, reduced)?
+        },
+        Command::Examples => return Err("the examples command cannot be benchmarked".into()),
+        Command::Eval => return Err("the eval command cannot be benchmarked".into()),
+        Command::Replay {
+            ..
+        } => return Err("the replay command cannot be benchmarked".into()),
+    };
+
+    Ok(output)
+}
+
+/// Returns the JSON format requested by the raw command line arguments, if any.
+///
+/// This is used to report errors in the arguments themselves, before they can
+/// be parsed into a [`Cli`].
+fn requested_json_format(args: &[String]) -> Option<Format> {
+    let mut format = None;
+    let mut json = None;
+    let mut args = args.iter().skip(1).map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            "--" => break,
+            "--json" => json = Some(Format::JsonPretty),
+            "--json-compact" => json = json.or(Some(Format::Json)),
+            "--format" => format = args.next().and_then(|f| Format::from_str(f, true).ok()),
+            _ => {
+                if let Some(f) = arg.strip_prefix("--format=") {
+                    format = Format::from_str(f, true).ok();
+                }
+            },
+        }
     }
 
-    Ok(())
+    json.or(format)
+        .filter(|f| matches!(f, Format::Json | Format::JsonPretty | Format::Ndjson))
 }
 
 fn main() {
-    if let Err(e) = run_cli() {
-        exit(e, 1);
+    let args = env::args().collect::<Vec<_>>();
+    let app = match Cli::try_parse_from(&args) {
+        Ok(app) => app,
+        // help and version requests aren't errors and are printed as usual
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => match requested_json_format(&args) {
+            Some(format) => {
+                // only the message is kept, without the usage and the hint to
+                // use `--help`
+                let msg = e.to_string();
+                let end = ["\n\nUSAGE:", "\n\nFor more information"]
+                    .iter()
+                    .filter_map(|s| msg.find(s))
+                    .min()
+                    .unwrap_or(msg.len());
+                exit(msg[..end].trim_start_matches("error: ").trim_end(), 2, format)
+            },
+            None => e.exit(),
+        },
+    };
+    set_stderr_color(color_choice(app.no_color, stderr().is_terminal()));
+    let format = app.format();
+    set_panic_hook(format);
+    if let Some(timeout) = app.timeout {
+        deadline::set(timeout);
+    }
+
+    if let Err(e) = run_cli(app) {
+        exit(e, 1, format);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders `selections` as plain text with the display the `count` and `all`
+    /// flags resolve to.
+    fn render_selections(selections: &[&str], count: bool, all: bool) -> String {
+        #[derive(Parser)]
+        struct Options {
+            #[clap(flatten)]
+            count_options: CountOptions,
+        }
+
+        let options = Options::parse_from(["rnd"]).count_options;
+        let display = SelectionDisplay::new(0, count, all, selections.len(), &options);
+        let output = selections_output(selections.iter().copied(), display).unwrap();
+        let mut buffer = Vec::new();
+        output.write(&mut buffer, Format::Plain).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    const FEW: [&str; 5] = ["b", "a", "b", "b", "c"];
+    const MANY: [&str; 11] = ["b", "a", "b", "b", "c", "a", "b", "c", "b", "a", "b"];
+
+    #[test]
+    fn few_selections_are_listed() {
+        assert_eq!(render_selections(&FEW, false, false), "b, a, b, b, c\n");
+        assert_eq!(render_selections(&FEW, false, true), "b, a, b, b, c\n");
+    }
+
+    #[test]
+    fn few_selections_are_listed_with_the_count() {
+        let tally = "b, a, b, b, c\n\nb: 3\na: 1\nc: 1\n";
+        assert_eq!(render_selections(&FEW, true, false), tally);
+        assert_eq!(render_selections(&FEW, true, true), tally);
+    }
+
+    #[test]
+    fn many_selections_are_counted() {
+        let counts = "b: 6\na: 3\nc: 2\n";
+        assert_eq!(render_selections(&MANY, false, false), counts);
+        assert_eq!(render_selections(&MANY, true, false), counts);
+    }
+
+    #[test]
+    fn many_selections_are_listed_with_all() {
+        let list = "b, a, b, b, c, a, b, c, b, a, b";
+        assert_eq!(render_selections(&MANY, false, true), format!("{list}\n"));
+        assert_eq!(render_selections(&MANY, true, true), format!("{list}\n\nb: 6\na: 3\nc: 2\n"));
+    }
+
+    #[test]
+    fn infer_range_without_bounds() {
+        assert_eq!(infer_range(None, None), (Num::FLOAT_0, Num::FLOAT_1));
+    }
+
+    #[test]
+    fn infer_range_with_non_negative_bound() {
+        assert_eq!(infer_range(Some(Num::Int(10)), None), (Num::INT_0, Num::Int(10)));
+        assert_eq!(infer_range(Some(Num::Int(0)), None), (Num::INT_0, Num::Int(0)));
+    }
+
+    #[test]
+    fn infer_range_with_negative_bound() {
+        assert_eq!(infer_range(Some(Num::Int(-10)), None), (Num::Int(-10), Num::INT_0));
+    }
+
+    #[test]
+    fn infer_range_with_both_bounds() {
+        assert_eq!(infer_range(Some(Num::Int(-3)), Some(Num::Int(7))), (Num::Int(-3), Num::Int(7)));
+    }
+
+    #[test]
+    fn infer_range_with_float_bound() {
+        // the inferred bound stays an integer, the range is a float range
+        // because of the given bound
+        assert_eq!(infer_range(Some(Num::Float(2.5)), None), (Num::INT_0, Num::Float(2.5)));
+        assert_eq!(infer_range(Some(Num::Float(-2.5)), None), (Num::Float(-2.5), Num::INT_0));
+        assert_eq!(infer_range(None, Some(Num::Float(2.5))), (Num::FLOAT_0, Num::Float(2.5)));
+    }
+
+    #[test]
+    fn infer_range_with_mixed_bounds() {
+        assert_eq!(
+            infer_range(Some(Num::Int(1)), Some(Num::Float(2.5))),
+            (Num::Int(1), Num::Float(2.5))
+        );
+        assert_eq!(
+            infer_range(Some(Num::Float(-1.5)), Some(Num::Int(2))),
+            (Num::Float(-1.5), Num::Int(2))
+        );
+    }
+
+    #[test]
+    fn item_range_len() {
+        let range = |s: &str| s.parse::<ItemRange>().unwrap();
+        assert_eq!(range("1:10").len(), Some(10));
+        assert_eq!(range("10:1:-3").len(), Some(4));
+        assert_eq!(range("-9223372036854775808:9223372036854775807:2").len(), Some(1 << 63));
+    }
+
+    #[test]
+    fn item_range_len_overflows_on_full_span() {
+        let range = "-9223372036854775808:9223372036854775807"
+            .parse::<ItemRange>()
+            .unwrap();
+        assert_eq!(range.len(), None);
+    }
+
+    #[test]
+    fn random_accepts_negative_bounds() {
+        let app = Cli::try_parse_from(["rnd", "random", "-5", "-1"]).unwrap();
+        let Some(Command::Random {
+            start,
+            end,
+            ..
+        }) = app.command
+        else {
+            panic!("expected the random command");
+        };
+        assert_eq!(infer_range(start, end), (Num::Int(-5), Num::Int(-1)));
     }
 }
@@ -0,0 +1,879 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io::{self, stderr, IsTerminal, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde::ser::{SerializeMap, SerializeSeq, Serializer};
+use serde::Serialize;
+use serde_json::{to_value, Map};
+use termcolor::{Ansi, Color, ColorSpec, NoColor, WriteColor};
+
+use crate::error::Result;
+
+/// The format in which the output of a command is printed.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Colored text if the output is a terminal, human-readable text otherwise.
+    Auto,
+    /// Human-readable text.
+    Plain,
+    /// Colored human-readable text.
+    Pretty,
+    /// Single-line JSON.
+    Json,
+    /// Indented JSON.
+    JsonPretty,
+    /// One JSON object for each sample or entry, on separate lines.
+    Ndjson,
+    /// YAML.
+    Yaml,
+    /// Packed unsigned 8-bit integers.
+    U8,
+    /// Packed little-endian unsigned 16-bit integers.
+    U16le,
+    /// Packed big-endian unsigned 16-bit integers.
+    U16be,
+    /// Packed little-endian unsigned 32-bit integers.
+    U32le,
+    /// Packed big-endian unsigned 32-bit integers.
+    U32be,
+    /// Packed little-endian unsigned 64-bit integers.
+    U64le,
+    /// Packed big-endian unsigned 64-bit integers.
+    U64be,
+}
+
+impl Format {
+    /// Returns the bytes of `i` in a packed integer format.
+    ///
+    /// Returns `None` if `i` doesn't fit in the format.
+    fn pack(self, i: i128) -> Option<Vec<u8>> {
+        match self {
+            Format::U8 => u8::try_from(i).ok().map(|n| vec![n]),
+            Format::U16le => u16::try_from(i).ok().map(|n| n.to_le_bytes().to_vec()),
+            Format::U16be => u16::try_from(i).ok().map(|n| n.to_be_bytes().to_vec()),
+            Format::U32le => u32::try_from(i).ok().map(|n| n.to_le_bytes().to_vec()),
+            Format::U32be => u32::try_from(i).ok().map(|n| n.to_be_bytes().to_vec()),
+            Format::U64le => u64::try_from(i).ok().map(|n| n.to_le_bytes().to_vec()),
+            Format::U64be => u64::try_from(i).ok().map(|n| n.to_be_bytes().to_vec()),
+            _ => None,
+        }
+    }
+}
+
+/// A single value in the output of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// An integer.
+    Int(i128),
+    /// A floating point number along with its formatted representation.
+    Float(f64, String),
+    /// A string.
+    Str(String),
+}
+
+impl Display for Value {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(i) => i.fmt(fmt),
+            Value::Float(_, text) => text.fmt(fmt),
+            Value::Str(s) => s.fmt(fmt),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Value::Int(i) => serializer.serialize_i128(*i),
+            Value::Float(f, _) => serializer.serialize_f64(*f),
+            Value::Str(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::Str(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::Str(s)
+    }
+}
+
+impl<T: Clone + Into<Value>> From<&T> for Value {
+    fn from(value: &T) -> Self {
+        value.clone().into()
+    }
+}
+
+impl From<usize> for Value {
+    fn from(i: usize) -> Self {
+        Self::Int(i as i128)
+    }
+}
+
+impl From<i128> for Value {
+    fn from(i: i128) -> Self {
+        Self::Int(i)
+    }
+}
+
+/// The number of times each item was selected.
+#[derive(Debug, Clone, Default)]
+pub struct Counts {
+    /// The items and their counts, in the order they are displayed.
+    pub entries: Vec<(String, u64)>,
+    /// The number of items left out of `entries`.
+    pub omitted: usize,
+    /// The total number of selections.
+    pub total: u64,
+    /// Whether the total is displayed after the counts.
+    pub show_total: bool,
+    /// The template of the lines of the counts, if any.
+    pub format: Option<CountFormat>,
+    /// The number of columns the entries are laid out in, top to bottom and then
+    /// left to right.
+    ///
+    /// Zero is the same as one column.
+    pub columns: usize,
+    /// The expected proportion of each item, in `0.0..=1.0`.
+    ///
+    /// The percentage of an item and its deviation from the expected percentage
+    /// are shown after its count if it has an expected proportion.
+    pub expected: HashMap<String, f64>,
+    /// Whether the counts are serialized as an array of `{"item", "count"}`
+    /// objects in display order, instead of an object of items to counts.
+    pub ordered: bool,
+}
+
+impl Counts {
+    /// Renders the counts to `w`.
+    fn render(&self, w: &mut impl WriteColor) -> io::Result<()> {
+        let columns = self.columns.clamp(1, self.entries.len().max(1));
+        let rows = self.entries.len().div_ceil(columns);
+
+        // every entry is rendered once without colors to measure the columns
+        let widths = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut buffer = NoColor::new(Vec::new());
+                self.render_entry(&mut buffer, entry)?;
+                Ok(String::from_utf8_lossy(&buffer.into_inner())
+                    .chars()
+                    .count())
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let column_widths = widths
+            .chunks(rows.max(1))
+            .map(|column| column.iter().copied().max().unwrap_or(0))
+            .collect::<Vec<_>>();
+
+        for row in 0..rows {
+            if row > 0 {
+                writeln!(w)?;
+            }
+            for column in 0..columns {
+                let i = column * rows + row;
+                let Some(entry) = self.entries.get(i) else {
+                    break;
+                };
+                if column > 0 {
+                    let padding = column_widths[column - 1] - widths[i - rows];
+                    write!(w, "{:padding$}  ", "")?;
+                }
+                self.render_entry(w, entry)?;
+            }
+        }
+        if self.omitted > 0 {
+            writeln!(w)?;
+            paint(w, format_args!("... and {} more", self.omitted), Style::Note)?;
+        }
+        if self.show_total {
+            writeln!(w)?;
+            paint(w, "total", Style::Note)?;
+            write!(w, ": ")?;
+            paint(w, self.total, Style::Count)?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders a single entry of the counts to `w`.
+    fn render_entry(
+        &self,
+        w: &mut impl WriteColor,
+        (item, count): &(String, u64),
+    ) -> io::Result<()> {
+        match &self.format {
+            Some(format) => format.render(w, item, *count, self.total),
+            None => {
+                paint(w, item, Style::Label)?;
+                write!(w, ": ")?;
+                paint(w, count, Style::Count)?;
+                if let Some(expected) = self.expected.get(item) {
+                    let percent = percent(*count, self.total);
+                    let deviation = percent - expected * 100.0;
+                    paint(w, format_args!(" ({percent:.2}%, {deviation:+.2}%)"), Style::Note)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// A template for the lines of [`Counts`].
+///
+/// The template can contain the `{item}`, `{count}` and `{percent}` placeholders.
+/// Use `{{` and `}}` for literal braces.
+#[derive(Debug, Clone)]
+pub struct CountFormat(Vec<Piece>);
+
+/// A part of a [`CountFormat`].
+#[derive(Debug, Clone)]
+enum Piece {
+    /// Literal text.
+    Text(String),
+    /// The item.
+    Item,
+    /// The number of times the item was selected.
+    Count,
+    /// The percentage of selections that were the item.
+    Percent,
+}
+
+impl FromStr for CountFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                },
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                },
+                '{' => {
+                    let (name, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .ok_or_else(|| "unclosed `{` in count format".to_owned())?;
+                    let piece = match name {
+                        "item" => Piece::Item,
+                        "count" => Piece::Count,
+                        "percent" => Piece::Percent,
+                        _ => return Err(format!("unknown placeholder `{{{name}}}`")),
+                    };
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    pieces.push(piece);
+                    chars = rest.chars();
+                },
+                '}' => return Err("unmatched `}` in count format".to_owned()),
+                c => text.push(c),
+            }
+        }
+        pieces.push(Piece::Text(text));
+
+        Ok(Self(pieces))
+    }
+}
+
+impl CountFormat {
+    /// Renders the line of `item` to `w`.
+    fn render(
+        &self,
+        w: &mut impl WriteColor,
+        item: &str,
+        count: u64,
+        total: u64,
+    ) -> io::Result<()> {
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => write!(w, "{text}")?,
+                Piece::Item => paint(w, item, Style::Label)?,
+                Piece::Count => paint(w, count, Style::Count)?,
+                Piece::Percent => {
+                    paint(w, format_args!("{:.2}", percent(count, total)), Style::Count)?
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the percentage of `total` that `count` is.
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64 * 100.0
+    }
+}
+
+impl Serialize for Counts {
+    /// Serializes the entries, along with the `total` and the number of
+    /// `omitted` items if they are displayed, under a `counts` key.
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if !self.show_total && self.omitted == 0 {
+            return CountEntries(self).serialize(serializer);
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("counts", &CountEntries(self))?;
+        if self.show_total {
+            map.serialize_entry("total", &self.total)?;
+        }
+        if self.omitted > 0 {
+            map.serialize_entry("omitted", &self.omitted)?;
+        }
+        map.end()
+    }
+}
+
+/// The entries of [`Counts`], serialized as an object of items to counts, or as
+/// an array of `{"item", "count"}` objects if the counts are ordered.
+struct CountEntries<'a>(&'a Counts);
+
+impl Serialize for CountEntries<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let counts = self.0;
+        #[derive(Serialize)]
+        struct Entry<'a> {
+            item: &'a str,
+            count: u64,
+        }
+
+        if counts.ordered {
+            let mut seq = serializer.serialize_seq(Some(counts.entries.len()))?;
+            for (item, count) in &counts.entries {
+                seq.serialize_element(&Entry {
+                    item,
+                    count: *count,
+                })?;
+            }
+            return seq.end();
+        }
+
+        let mut map = serializer.serialize_map(Some(counts.entries.len()))?;
+        for (item, count) in &counts.entries {
+            map.serialize_entry(item, count)?;
+        }
+        map.end()
+    }
+}
+
+/// The output of a command.
+///
+/// Every command produces one of these, which is then printed in the requested
+/// [`Format`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Output {
+    /// Nothing.
+    Empty,
+    /// A single value.
+    Value(Value),
+    /// A list of values.
+    List(Vec<Value>),
+    /// A list of values displayed on separate lines.
+    Lines(Vec<Value>),
+    /// A list of values displayed on separate lines without any colors, for
+    /// piping into other tools.
+    Raw(Vec<Value>),
+    /// The number of times each item was selected.
+    Counts(Counts),
+    /// Every selection along with the number of times each item was selected.
+    ///
+    /// As text, the selections and the counts are separated by exactly one blank
+    /// line.
+    Tally {
+        /// The selections in order.
+        selections: Vec<Value>,
+        /// The number of times each item was selected.
+        counts: Counts,
+    },
+    /// Pairs of items assigned to each other.
+    #[serde(serialize_with = "serialize_pairs")]
+    Pairs(Vec<(String, String)>),
+    /// Several outputs displayed one after another.
+    #[serde(serialize_with = "serialize_sections")]
+    Sections(Vec<Section>),
+}
+
+/// A named part of an [`Output::Sections`].
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// The key of the section in structured formats.
+    pub key: &'static str,
+    /// The heading displayed above the section in plain text, if any.
+    pub heading: Option<&'static str>,
+    /// The output of the section.
+    pub output: Output,
+}
+
+impl Section {
+    /// Renders the section to `w`.
+    fn render(&self, w: &mut impl WriteColor) -> io::Result<()> {
+        if let Some(heading) = self.heading {
+            paint(w, heading, Style::Heading)?;
+            writeln!(w, ":")?;
+        }
+        self.output.render(w)
+    }
+}
+
+/// Serializes the pairs of an [`Output::Pairs`] as a map of the left items to the
+/// right items.
+///
+/// If a left item appears more than once, its last pair is used.
+fn serialize_pairs<S: Serializer>(
+    pairs: &[(String, String)],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut indices = HashMap::new();
+    let mut entries = Vec::new();
+    for (left, right) in pairs {
+        match indices.get(left) {
+            Some(&i) => entries[i] = (left, right),
+            None => {
+                indices.insert(left, entries.len());
+                entries.push((left, right));
+            },
+        }
+    }
+
+    let mut map = serializer.serialize_map(Some(entries.len()))?;
+    for (left, right) in entries {
+        map.serialize_entry(left, right)?;
+    }
+    map.end()
+}
+
+/// Serializes the sections of an [`Output::Sections`] as a map of their keys to
+/// their outputs.
+fn serialize_sections<S: Serializer>(
+    sections: &[Section],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(sections.len()))?;
+    for section in sections {
+        map.serialize_entry(section.key, &section.output)?;
+    }
+    map.end()
+}
+
+impl Output {
+    /// Converts a single value or a list of values into an [`Output::Raw`].
+    ///
+    /// Other outputs are returned unchanged.
+    pub fn into_raw(self) -> Self {
+        match self {
+            Output::Value(v) => Output::Raw(vec![v]),
+            Output::List(values) | Output::Lines(values) => Output::Raw(values),
+            output => output,
+        }
+    }
+
+    /// Writes the output to `writer` in the given format.
+    ///
+    /// [`Format::Auto`] is written as [`Format::Plain`], it should be resolved
+    /// beforehand.
+    pub fn write(&self, writer: &mut impl Write, format: Format) -> Result<()> {
+        if let Output::Empty = self {
+            return Ok(());
+        }
+
+        match format {
+            Format::Auto | Format::Plain => {
+                let mut writer = NoColor::new(writer);
+                self.render(&mut writer)?;
+                writeln!(writer)?;
+            },
+            Format::Pretty => {
+                let mut writer = Ansi::new(writer);
+                self.render(&mut writer)?;
+                writeln!(writer)?;
+            },
+            Format::Json => {
+                serde_json::to_writer(&mut *writer, self)?;
+                writeln!(writer)?;
+            },
+            Format::JsonPretty => {
+                serde_json::to_writer_pretty(&mut *writer, self)?;
+                writeln!(writer)?;
+            },
+            Format::Ndjson => {
+                self.write_records(writer, None)?;
+            },
+            Format::Yaml => serde_yaml::to_writer(writer, self)?,
+            Format::U8
+            | Format::U16le
+            | Format::U16be
+            | Format::U32le
+            | Format::U32be
+            | Format::U64le
+            | Format::U64be => self.write_packed(writer, format)?,
+        }
+
+        Ok(())
+    }
+
+    /// Writes the integers of the output to `writer` as raw bytes in a packed
+    /// integer format.
+    ///
+    /// Returns an error if the output isn't a list of integers that fit in the format.
+    /// Every value is packed before anything is written, so an error leaves no
+    /// partial output.
+    fn write_packed(&self, writer: &mut impl Write, format: Format) -> Result<()> {
+        let values = match self {
+            Output::Value(v) => std::slice::from_ref(v),
+            Output::List(list) | Output::Lines(list) | Output::Raw(list) => list,
+            _ => return Err("only lists of integers can be written as packed integers".into()),
+        };
+
+        let mut bytes = Vec::new();
+        for value in values {
+            let Value::Int(i) = value else {
+                return Err(format!("`{value}` is not an integer").into());
+            };
+            let packed = format.pack(*i).ok_or_else(|| {
+                let name = format.to_possible_value().map(|v| v.get_name().to_owned());
+                format!("`{i}` doesn't fit in the `{}` format", name.unwrap_or_default())
+            })?;
+            bytes.extend_from_slice(&packed);
+        }
+        writer.write_all(&bytes)?;
+
+        Ok(())
+    }
+
+    /// Writes one JSON object per line for each sample or entry of the output.
+    ///
+    /// Records are written as they are built, so nothing is collected first. The
+    /// records of a section have a `section` field with the key of the section.
+    fn write_records(&self, writer: &mut impl Write, section: Option<&str>) -> Result<()> {
+        // the total and the number of omitted items get records of their own
+        // after the entries, if they are displayed
+        fn counts_records(
+            c: &Counts,
+            record: &mut impl FnMut(Vec<(&str, serde_json::Value)>) -> Result<()>,
+        ) -> Result<()> {
+            for (item, count) in &c.entries {
+                record(vec![("item", item.as_str().into()), ("count", (*count).into())])?;
+            }
+            if c.show_total {
+                record(vec![("total", c.total.into())])?;
+            }
+            if c.omitted > 0 {
+                record(vec![("omitted", c.omitted.into())])?;
+            }
+            Ok(())
+        }
+
+        let mut record = |fields: Vec<(&str, serde_json::Value)>| -> Result<()> {
+            let mut record = fields
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect::<Map<_, _>>();
+            if let Some(key) = section {
+                record.insert("section".to_owned(), key.into());
+            }
+            serde_json::to_writer(&mut *writer, &record)?;
+            writeln!(writer)?;
+            Ok(())
+        };
+
+        match self {
+            Output::Empty => Ok(()),
+            Output::Value(v) => record(vec![("value", to_value(v)?)]),
+            Output::List(list) | Output::Lines(list) | Output::Raw(list) => list
+                .iter()
+                .try_for_each(|v| record(vec![("value", to_value(v)?)])),
+            Output::Counts(c) => counts_records(c, &mut record),
+            Output::Tally {
+                selections,
+                counts: c,
+            } => {
+                selections
+                    .iter()
+                    .try_for_each(|v| record(vec![("value", to_value(v)?)]))?;
+                counts_records(c, &mut record)
+            },
+            Output::Pairs(pairs) => pairs.iter().try_for_each(|(left, right)| {
+                record(vec![("left", left.as_str().into()), ("right", right.as_str().into())])
+            }),
+            Output::Sections(sections) => sections
+                .iter()
+                .try_for_each(|s| s.output.write_records(writer, Some(s.key))),
+        }
+    }
+
+    /// Renders the output as text to `w`.
+    fn render(&self, w: &mut impl WriteColor) -> io::Result<()> {
+        match self {
+            Output::Empty => Ok(()),
+            Output::Value(v) => paint(w, v, Style::Item),
+            Output::List(list) => render_list(w, list, ", "),
+            Output::Lines(lines) => render_list(w, lines, "\n"),
+            Output::Raw(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(w)?;
+                    }
+                    write!(w, "{value}")?;
+                }
+                Ok(())
+            },
+            Output::Counts(counts) => counts.render(w),
+            Output::Tally {
+                selections,
+                counts,
+            } => {
+                render_list(w, selections, ", ")?;
+                write!(w, "\n\n")?;
+                counts.render(w)
+            },
+            Output::Pairs(pairs) => {
+                for (i, (left, right)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(w)?;
+                    }
+                    paint(w, left, Style::Label)?;
+                    write!(w, ": ")?;
+                    paint(w, right, Style::Item)?;
+                }
+                Ok(())
+            },
+            Output::Sections(sections) => {
+                for (i, section) in sections.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, "\n\n")?;
+                    }
+                    section.render(w)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// The styles used to render outputs.
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    /// A selected item or generated value.
+    Item,
+    /// The label of an item in a count or assignment.
+    Label,
+    /// A count.
+    Count,
+    /// Some additional information.
+    Note,
+    /// The heading of a section.
+    Heading,
+}
+
+impl Style {
+    /// Returns the color spec of the style.
+    fn spec(self) -> ColorSpec {
+        let mut spec = ColorSpec::new();
+        match self {
+            Style::Item => spec.set_fg(Some(Color::Green)),
+            Style::Label => spec.set_fg(Some(Color::Cyan)),
+            Style::Count => spec.set_bold(true),
+            Style::Note => spec.set_dimmed(true),
+            Style::Heading => spec.set_bold(true).set_underline(true),
+        };
+        spec
+    }
+}
+
+/// Writes `text` to `w` in the given style.
+fn paint(w: &mut impl WriteColor, text: impl Display, style: Style) -> io::Result<()> {
+    w.set_color(&style.spec())?;
+    write!(w, "{text}")?;
+    w.reset()
+}
+
+/// Writes the values to `w`, separated by `separator`.
+fn render_list(w: &mut impl WriteColor, values: &[Value], separator: &str) -> io::Result<()> {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            write!(w, "{separator}")?;
+        }
+        paint(w, value, Style::Item)?;
+    }
+
+    Ok(())
+}
+
+/// A histogram of counts that is redrawn in place on the `stderr`.
+pub struct LiveHistogram {
+    /// The number of lines drawn the last time.
+    lines: usize,
+    /// The time of the last draw.
+    last_draw: Option<Instant>,
+}
+
+impl LiveHistogram {
+    /// The minimum time between two draws.
+    const INTERVAL: Duration = Duration::from_millis(50);
+    /// The width of the longest bar.
+    const WIDTH: u64 = 40;
+
+    /// Creates a new histogram, or returns `None` if the `stderr` is not a terminal.
+    pub fn new() -> Option<Self> {
+        stderr().is_terminal().then_some(Self {
+            lines: 0,
+            last_draw: None,
+        })
+    }
+
+    /// Redraws the histogram if enough time has passed since the last draw, or
+    /// always if `force` is true.
+    pub fn update<D: Display>(&mut self, counts: &HashMap<D, u64>, force: bool) {
+        if !force && self.last_draw.is_some_and(|t| t.elapsed() < Self::INTERVAL) {
+            return;
+        }
+
+        let entries = counts
+            .iter()
+            .map(|(s, c)| (s.to_string(), *c))
+            .sorted()
+            .collect::<Vec<_>>();
+        let max = entries.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1);
+        let label_width = entries.iter().map(|(s, _)| s.len()).max().unwrap_or(0);
+
+        let mut stderr = stderr().lock();
+        // move the cursor to the first line of the previous histogram
+        if self.lines > 0 {
+            let _ = write!(stderr, "\x1b[{}A", self.lines);
+        }
+        for (label, count) in &entries {
+            let bar = "#".repeat((count * Self::WIDTH / max) as usize);
+            let _ = writeln!(stderr, "\x1b[2K{label:>label_width$} | {bar} {count}");
+        }
+        let _ = stderr.flush();
+
+        self.lines = entries.len();
+        self.last_draw = Some(Instant::now());
+    }
+}
+
+/// Running counts of selections that are printed on the `stderr` every `interval`
+/// selections.
+pub struct CountSnapshots<D> {
+    /// The number of selections between two snapshots.
+    interval: usize,
+    /// The number of selections recorded so far.
+    seen: usize,
+    /// The number of times each item was selected so far.
+    counts: HashMap<D, u64>,
+}
+
+impl<D: Clone + Display + Ord + Hash> CountSnapshots<D> {
+    /// Creates new running counts that are printed every `interval` selections.
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval,
+            seen: 0,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Records a selection, printing the counts so far if a full interval has passed.
+    pub fn record(&mut self, selection: &D) {
+        *self.counts.entry(selection.clone()).or_insert(0) += 1;
+        self.seen += 1;
+
+        if self.seen.is_multiple_of(self.interval) {
+            let counts = self
+                .counts
+                .iter()
+                .sorted()
+                .map(|(s, c)| format!("{s}: {c}"))
+                .join(", ");
+            eprintln!("after {}: {counts}", self.seen);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(output: &Output) -> String {
+        let mut buffer = Vec::new();
+        output.write(&mut buffer, Format::Plain).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    fn counts(entries: &[(&str, u64)]) -> Counts {
+        Counts {
+            entries: entries
+                .iter()
+                .map(|&(item, count)| (item.to_owned(), count))
+                .collect(),
+            total: entries.iter().map(|&(_, count)| count).sum(),
+            ..Default::default()
+        }
+    }
+
+    fn values(items: &[&str]) -> Vec<Value> {
+        items.iter().map(|&item| item.into()).collect()
+    }
+
+    #[test]
+    fn renders_counts() {
+        let output = Output::Counts(counts(&[("b", 3), ("a", 1), ("c", 1)]));
+        assert_eq!(plain(&output), "b: 3\na: 1\nc: 1\n");
+    }
+
+    #[test]
+    fn renders_counts_with_total_and_omitted() {
+        let mut counts = counts(&[("b", 3), ("a", 1)]);
+        counts.omitted = 1;
+        counts.total = 5;
+        counts.show_total = true;
+        let output = Output::Counts(counts);
+        assert_eq!(plain(&output), "b: 3\na: 1\n... and 1 more\ntotal: 5\n");
+    }
+
+    #[test]
+    fn renders_counts_in_columns() {
+        let mut counts = counts(&[("bb", 3), ("a", 1), ("c", 1)]);
+        counts.columns = 2;
+        let output = Output::Counts(counts);
+        assert_eq!(plain(&output), "bb: 3  c: 1\na: 1\n");
+    }
+
+    #[test]
+    fn renders_list() {
+        let output = Output::List(values(&["b", "a", "b", "b", "c"]));
+        assert_eq!(plain(&output), "b, a, b, b, c\n");
+    }
+
+    #[test]
+    fn renders_tally() {
+        let output = Output::Tally {
+            selections: values(&["b", "a", "b", "b", "c"]),
+            counts: counts(&[("b", 3), ("a", 1), ("c", 1)]),
+        };
+        assert_eq!(plain(&output), "b, a, b, b, c\n\nb: 3\na: 1\nc: 1\n");
+    }
+}
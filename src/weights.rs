@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use rand::distributions::{Distribution, WeightedError};
+use rand::Rng;
+
+use crate::error::Result;
+
+/// Parses a list of weights separated by `delimiter`.
+///
+/// Whitespace around the weights is ignored, as are empty weights, so repeated
+/// spaces can separate the weights. A weight can be a fraction like `1/3`, and
+/// a list without `delimiter` can be a ratio like `2:1:1`.
+pub fn parse(weights: &str, delimiter: char) -> Result<Vec<f64>> {
+    let delimiter =
+        if !weights.contains(delimiter) && weights.contains(':') { ':' } else { delimiter };
+
+    weights
+        .split(delimiter)
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .map(parse_weight)
+        .collect()
+}
+
+/// Parses a single weight, which is a number or a fraction of two numbers.
+fn parse_weight(weight: &str) -> Result<f64> {
+    let invalid = || format!("invalid weight `{weight}`");
+    let number = |n: &str| n.trim().parse::<f64>().map_err(|_| invalid());
+
+    match weight.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator = number(denominator)?;
+            if denominator == 0.0 {
+                return Err(format!("the divisor of weight `{weight}` must not be zero").into());
+            }
+            Ok(number(numerator)? / denominator)
+        },
+        None => Ok(number(weight)?),
+    }
+}
+
+/// Returns an error naming the first weight that is NaN or infinite, if any.
+pub fn check_finite(weights: &[f64]) -> Result<()> {
+    match weights.iter().position(|w| !w.is_finite()) {
+        Some(i) => Err(format!("weight {i} (`{}`) must be a finite number", weights[i]).into()),
+        None => Ok(()),
+    }
+}
+
+/// Returns an error if there isn't exactly one weight for each item.
+pub fn check_len<T>(items: &[T], weights: &[f64]) -> Result<()> {
+    if items.len() != weights.len() {
+        return Err("the number of weights must be equal to the number of items".into());
+    }
+    Ok(())
+}
+
+/// Converts cumulative weights into the weight of each item.
+///
+/// The cumulative weights must be non-decreasing.
+pub fn from_cumulative(cumulative: &[f64]) -> Result<Vec<f64>> {
+    let mut previous = 0.0;
+    let mut weights = Vec::with_capacity(cumulative.len());
+
+    for &c in cumulative {
+        if c < previous {
+            return Err("cumulative weights must be non-decreasing".into());
+        }
+        weights.push(c - previous);
+        previous = c;
+    }
+
+    Ok(weights)
+}
+
+/// Converts weights on a log scale into linear weights, so `w` becomes `10^w`.
+pub fn from_log10(weights: &[f64]) -> Result<Vec<f64>> {
+    weights
+        .iter()
+        .map(|w| {
+            let weight = 10f64.powf(*w);
+            if weight.is_finite() {
+                Ok(weight)
+            } else {
+                Err(format!("log weight `{w}` is too large").into())
+            }
+        })
+        .collect()
+}
+
+/// Transforms `weights` with the softmax function at the given `temperature`.
+///
+/// Each weight `w` becomes `exp(w / temperature)`, normalized so that all the
+/// weights sum to 1.
+pub fn softmax(weights: &[f64], temperature: f64) -> Result<Vec<f64>> {
+    if !(temperature > 0.0 && temperature.is_finite()) {
+        return Err("temperature must be a positive number".into());
+    }
+
+    // subtracting the largest weight doesn't change the result, but prevents
+    // `exp` from overflowing for large weights
+    let max = weights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let exps = weights
+        .iter()
+        .map(|w| ((w - max) / temperature).exp())
+        .collect::<Vec<_>>();
+    let sum = exps.iter().sum::<f64>();
+
+    Ok(exps.into_iter().map(|e| e / sum).collect())
+}
+
+/// Removes repeated items, using the number of times each item appears as its weight.
+///
+/// The items are kept in the order of their first appearance.
+pub fn by_frequency(items: Vec<String>) -> (Vec<String>, Vec<f64>) {
+    let mut indices = HashMap::new();
+    let mut unique = Vec::new();
+    let mut weights = Vec::new();
+
+    for item in items {
+        match indices.get(&item) {
+            Some(&i) => weights[i] += 1.0,
+            None => {
+                indices.insert(item.clone(), unique.len());
+                unique.push(item);
+                weights.push(1.0);
+            },
+        }
+    }
+
+    (unique, weights)
+}
+
+/// Divides each weight by the sum of all the weights.
+///
+/// Returns `None` if the weights don't sum to a positive number.
+pub fn normalize(weights: &[f64]) -> Option<Vec<f64>> {
+    let sum = weights.iter().sum::<f64>();
+
+    (sum > 0.0 && sum.is_finite()).then(|| weights.iter().map(|w| w / sum).collect())
+}
+
+/// Splits `total` into whole parts proportional to `proportions`, which must sum
+/// to 1, so the parts always sum to `total`.
+///
+/// Every part is rounded down, and the parts with the largest remainders get the
+/// units that are left (the largest remainder method).
+pub fn apportion(proportions: &[f64], total: u64) -> Vec<u64> {
+    let exact = proportions
+        .iter()
+        .map(|p| p * total as f64)
+        .collect::<Vec<_>>();
+    let mut parts = exact.iter().map(|e| e.floor() as u64).collect::<Vec<_>>();
+
+    let left = total.saturating_sub(parts.iter().sum());
+    let mut by_remainder = (0..parts.len()).collect::<Vec<_>>();
+    by_remainder
+        .sort_by(|&a, &b| (exact[b] - exact[b].floor()).total_cmp(&(exact[a] - exact[a].floor())));
+    for &i in by_remainder.iter().take(left as usize) {
+        parts[i] += 1;
+    }
+
+    parts
+}
+
+/// A table for choosing weighted indices in constant time (Walker's alias
+/// method).
+///
+/// Building the table takes linear time, so this is faster than a
+/// [`WeightedIndex`](rand::distributions::WeightedIndex) when choosing many
+/// times from many weights.
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+    /// The probability of keeping each index instead of its alias.
+    keep: Vec<f64>,
+    /// The index chosen instead of each index when it isn't kept.
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Builds the table for `weights`, which must be non-negative and not all
+    /// zero.
+    pub fn new(weights: &[f64]) -> Result<Self> {
+        if weights.is_empty() {
+            return Err(WeightedError::NoItem.into());
+        }
+        if weights.iter().any(|w| w.is_nan() || *w < 0.0) {
+            return Err(WeightedError::InvalidWeight.into());
+        }
+        let sum = weights.iter().sum::<f64>();
+        if sum <= 0.0 {
+            return Err(WeightedError::AllWeightsZero.into());
+        }
+
+        let n = weights.len();
+        let mut keep = weights
+            .iter()
+            .map(|w| w * n as f64 / sum)
+            .collect::<Vec<_>>();
+        let mut alias = (0..n).collect::<Vec<_>>();
+
+        let (mut small, mut large): (Vec<_>, Vec<_>) = (0..n).partition(|&i| keep[i] < 1.0);
+        // every small index takes the rest of its probability from a large one
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            alias[s] = l;
+            keep[l] -= 1.0 - keep[s];
+            if keep[l] < 1.0 {
+                small.push(large.pop().unwrap());
+            }
+        }
+        // the indices left over are only off from 1 by rounding errors
+        for i in small.into_iter().chain(large) {
+            keep[i] = 1.0;
+        }
+
+        Ok(Self {
+            keep,
+            alias,
+        })
+    }
+}
+
+impl Distribution<usize> for AliasTable {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = rng.gen_range(0..self.keep.len());
+        if rng.gen::<f64>() < self.keep[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+/// Reads items and their weights from a CSV file of `label,weight` rows.
+///
+/// Every weight must be a non-negative number. If the weight of the first row
+/// isn't a number, like in `label,count`, the row is skipped as a header.
+pub fn from_csv(path: &Path) -> Result<(Vec<String>, Vec<f64>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("failed to open `{}`: {e}", path.display()))?;
+
+    let mut items = Vec::new();
+    let mut weights = Vec::new();
+
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        let (Some(label), Some(weight), None) = (record.get(0), record.get(1), record.get(2))
+        else {
+            return Err(
+                format!("line {line} of `{}` is not a `label,weight` row", path.display()).into()
+            );
+        };
+        if i == 0 && weight.trim().parse::<f64>().is_err() {
+            continue;
+        }
+        let weight = weight
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|w| *w >= 0.0 && w.is_finite())
+            .ok_or_else(|| {
+                format!("invalid weight `{weight}` on line {line} of `{}`", path.display())
+            })?;
+
+        items.push(label.to_owned());
+        weights.push(weight);
+    }
+
+    Ok((items, weights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_finite_accepts_finite_weights() {
+        assert!(check_finite(&[0.0, 1.5, -2.0, f64::MAX]).is_ok());
+    }
+
+    #[test]
+    fn check_finite_reports_nan() {
+        let err = check_finite(&[1.0, f64::NAN, 2.0]).unwrap_err();
+        assert_eq!(err.to_string(), "weight 1 (`NaN`) must be a finite number");
+    }
+
+    #[test]
+    fn check_finite_reports_infinity() {
+        let err = check_finite(&[1.0, 2.0, f64::INFINITY]).unwrap_err();
+        assert_eq!(err.to_string(), "weight 2 (`inf`) must be a finite number");
+    }
+
+    #[test]
+    fn check_finite_reports_negative_infinity() {
+        let err = check_finite(&[f64::NEG_INFINITY, 1.0]).unwrap_err();
+        assert_eq!(err.to_string(), "weight 0 (`-inf`) must be a finite number");
+    }
+
+    #[test]
+    fn check_finite_reports_first_non_finite_weight() {
+        let err = check_finite(&[1.0, f64::INFINITY, f64::NAN]).unwrap_err();
+        assert_eq!(err.to_string(), "weight 1 (`inf`) must be a finite number");
+    }
+}